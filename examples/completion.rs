@@ -48,20 +48,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut sh = pomprt::with(MiniShell, "% ");
 
     loop {
-        let ok = match sh.read() {
+        let code = match sh.read() {
             Ok(input) => {
                 let mut args = input.split_ascii_whitespace();
                 if let Some(cmd) = args.next() {
-                    Command::new(cmd).args(args).spawn()?.wait()?.success()
+                    Command::new(cmd)
+                        .args(args)
+                        .spawn()?
+                        .wait()?
+                        .code()
+                        .unwrap_or(1)
                 } else {
-                    true
+                    0
                 }
             }
-            Err(pomprt::Interrupt) => false,
+            Err(pomprt::Interrupt) => 130,
             Err(pomprt::Eof) => return Ok(()),
             Err(e) => Err(e)?,
         };
 
-        sh.set_prompt(if ok { "% " } else { "! " });
+        if code == 0 {
+            sh.set_prompt("% ");
+        } else {
+            sh.set_prompt(format!("{code} % "));
+        }
     }
 }
@@ -0,0 +1,18 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+fn main() -> Result<(), pomprt::Error> {
+    let fish = [
+        "salmon", "tuna", "cod", "mackerel", "trout", "herring", "carp", "eel",
+    ];
+
+    let picked = pomprt::new("favourite fish: ").select(&fish)?;
+    let others = pomprt::new("also like: ").multi_select(&fish)?;
+    if pomprt::new("save choices? ").confirm(true)? {
+        println!("{} and {others:?}", fish[picked]);
+    }
+
+    Ok(())
+}
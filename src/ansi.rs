@@ -40,6 +40,57 @@ pub enum Ansi<'a> {
     Csi(&'a [u8]),
 }
 
+/// A terminal's reply to a query
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Reply {
+    /// Cursor position report (CPR), answering DSR 6. Rows and columns start at 1
+    ///
+    /// These look just like some keys with modifiers, e.g. Shift-F3 is `CSI 1;2R`
+    Position { row: u16, col: u16 },
+    /// Report mode (DECRPM), answering DECRQM for a private mode
+    Mode { mode: u16, state: u8 },
+    /// Primary device attributes (DA1)
+    Attributes,
+}
+
+impl Reply {
+    /// Parses a sequence as a reply, if it looks like one
+    pub(crate) fn parse(seq: Ansi) -> Option<Self> {
+        let Ansi::Csi(seq) = seq else {
+            return None;
+        };
+        match seq {
+            [b'?', .., b'c'] => Some(Self::Attributes),
+            [b'?', params @ .., b'$', b'y'] => {
+                let [mode, state] = parse_params(params)?;
+                Some(Self::Mode {
+                    mode,
+                    state: state.try_into().ok()?,
+                })
+            }
+            [params @ .., b'R'] => {
+                let [row, col] = parse_params(params)?;
+                Some(Self::Position { row, col })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parses exactly `N` numeric parameters separated by `;`
+fn parse_params<const N: usize>(params: &[u8]) -> Option<[u16; N]> {
+    let mut out = [0; N];
+    let mut split = std::str::from_utf8(params).ok()?.split(';');
+    for n in &mut out {
+        let param = split.next()?;
+        if !param.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        *n = param.parse().ok()?;
+    }
+    split.next().is_none().then_some(out)
+}
+
 const ESC: u8 = b'[' ^ 0x40;
 const DEL: u8 = b'?' ^ 0x40;
 
@@ -58,6 +109,17 @@ impl<R: Read> Reader<R> {
         }
     }
 
+    /// Raw bytes making up the last sequence read
+    pub(crate) fn raw(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Zeroes the bytes kept from the last sequence, for secret input
+    pub(crate) fn wipe(&mut self) {
+        self.buffer.clear();
+        crate::state::zero_spare(&mut self.buffer);
+    }
+
     #[inline]
     fn next_byte(&mut self) -> io::Result<u8> {
         let p = self.buffer.len();
@@ -67,7 +129,7 @@ impl<R: Read> Reader<R> {
     }
 
     /// Reads a single [Ansi] sequence from input
-    pub fn read_sequence(&mut self) -> io::Result<Ansi> {
+    pub fn read_sequence(&mut self) -> io::Result<Ansi<'_>> {
         self.buffer.clear();
         Ok(match self.next_byte()? {
             b @ 0x80.. => {
@@ -79,7 +141,7 @@ impl<R: Read> Reader<R> {
                     0xc0.. => 2,
                     _ => 1, // will fail
                 };
-                self.buffer.extend([0; 3]);
+                self.buffer.resize(size, 0);
                 self.input.read_exact(&mut self.buffer[1..size])?;
                 let str = std::str::from_utf8(&self.buffer[0..size]).unwrap();
                 let char = str.chars().next().unwrap();
@@ -99,3 +161,40 @@ impl<R: Read> Reader<R> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(input: &[u8]) -> Option<Reply> {
+        Reply::parse(Reader::new(input).read_sequence().unwrap())
+    }
+
+    #[test]
+    fn replies() {
+        assert_eq!(
+            reply(b"\x1b[12;40R"),
+            Some(Reply::Position { row: 12, col: 40 })
+        );
+        assert_eq!(
+            reply(b"\x1b[?2026;2$y"),
+            Some(Reply::Mode {
+                mode: 2026,
+                state: 2
+            })
+        );
+        assert_eq!(reply(b"\x1b[?62;22c"), Some(Reply::Attributes));
+        assert_eq!(reply(b"\x1b[?1c"), Some(Reply::Attributes));
+    }
+
+    #[test]
+    fn not_replies() {
+        assert_eq!(reply(b"\x1b[A"), None);
+        assert_eq!(reply(b"\x1b[3~"), None);
+        assert_eq!(reply(b"\x1b[R"), None);
+        assert_eq!(reply(b"\x1b[1;+2R"), None);
+        assert_eq!(reply(b"\x1b[1;2;3R"), None);
+        assert_eq!(reply(b"\x1b[?2026$y"), None);
+        assert_eq!(reply(b"R"), None);
+    }
+}
@@ -15,6 +15,17 @@ pub struct Completion(
     pub Vec<String>,
 );
 
+/// Validation result returned by [`Editor::validate`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Validation {
+    /// The input may be submitted
+    Valid,
+    /// The input is continued on another line
+    Incomplete,
+    /// The input can't be submitted. The message is shown in place of the [hint][Editor::hint]
+    Invalid(String),
+}
+
 /// Edit event emitted by [`Editor::next_event`] to [`crate::Prompt`]
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Event {
@@ -23,6 +34,8 @@ pub enum Event {
     /// See also [`Editor::insert`]
     Insert(char),
     /// Enter key. Submits the current input or inserts a newline if [`Editor::is_multiline`] is `true`
+    ///
+    /// See also [`Editor::validate`]
     Enter,
     /// Removes the character behind the cursor
     Backspace,
@@ -101,6 +114,26 @@ pub trait Editor {
         hint.to_owned()
     }
 
+    /// Highlights a choice in [`Prompt::select`][crate::Prompt::select] and
+    /// [`Prompt::multi_select`][crate::Prompt::multi_select]. `current` is `true` for the one
+    /// under the cursor.
+    ///
+    /// See [`Editor::highlight`] for more information.
+    fn highlight_choice(&self, choice: &str, current: bool) -> String {
+        if current {
+            format!("\x1b[1m{choice}")
+        } else {
+            choice.to_owned()
+        }
+    }
+
+    /// Highlights the [placeholder][crate::Prompt::set_placeholder] shown while the input is empty.
+    ///
+    /// See [`Editor::highlight`] for more information.
+    fn highlight_placeholder(&self, placeholder: &str) -> String {
+        format!("\x1b[2m{placeholder}")
+    }
+
     /// Provides completion if available.
     ///
     /// Returning [`Some`] will cause [`Event::Tab`] to cycle through all results in the [`Vec`],
@@ -125,6 +158,27 @@ pub trait Editor {
         false
     }
 
+    /// Checks whether the input may be submitted, after [`Editor::is_multiline`] returns `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pomprt::*;
+    /// # struct Port;
+    /// # impl Editor for Port {
+    /// fn validate(&self, buffer: &str) -> Validation {
+    ///     match buffer.parse::<u16>() {
+    ///         Ok(_) => Validation::Valid,
+    ///         Err(e) => Validation::Invalid(format!("invalid port: {e}")),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    fn validate(&self, buffer: &str) -> Validation {
+        let _ = buffer;
+        Validation::Valid
+    }
+
     /// Returns `true` if the given character is a word character.
     ///
     /// This affects word movement keybinds (e.g. Ctrl-Right).
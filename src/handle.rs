@@ -0,0 +1,205 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+use crate::sys;
+
+/// State shared between a [`Prompt`][crate::Prompt] and its handles
+#[derive(Default)]
+pub(crate) struct Shared {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Whether a read is in progress
+    active: bool,
+    /// Whether the read in progress blocks in [`sys::wait`], rather than being an async read
+    waits: bool,
+    messages: Vec<String>,
+    cancelled: bool,
+    /// Task to wake for async reads
+    waker: Option<Waker>,
+}
+
+impl State {
+    /// Wakes up the read in progress. For async reads, the task's waker is returned instead, to
+    /// be woken once the lock is released, since the task might be polled right away
+    fn wake(&mut self) -> Option<Waker> {
+        if self.waits {
+            sys::wake();
+        }
+        self.waker.take()
+    }
+}
+
+impl Shared {
+    /// Marks a read as in progress until the returned guard is dropped. `waits` is whether it
+    /// blocks in [`sys::wait`], and needs to be woken up from it
+    pub fn begin(self: &Arc<Self>, waits: bool) -> Active {
+        let mut state = self.state.lock().unwrap();
+        state.active = true;
+        state.waits = waits;
+        // a cancel only applies to the read it was sent during
+        state.cancelled = false;
+        Active(Arc::clone(self))
+    }
+
+    /// Takes all messages sent by [`ExternalPrinter`]s
+    pub fn take_messages(&self) -> Vec<String> {
+        std::mem::take(&mut self.state.lock().unwrap().messages)
+    }
+
+    /// Returns `true` if a [`CancelHandle`] was used since the last call
+    pub fn take_cancel(&self) -> bool {
+        std::mem::take(&mut self.state.lock().unwrap().cancelled)
+    }
+
+    /// Sets the task to wake when a handle is used
+    pub fn register(&self, waker: &Waker) {
+        let mut state = self.state.lock().unwrap();
+        match &state.waker {
+            Some(w) if w.will_wake(waker) => {}
+            _ => state.waker = Some(waker.clone()),
+        }
+    }
+}
+
+pub(crate) struct Active(Arc<Shared>);
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.active = false;
+        state.waker = None;
+        // anything sent right as the read finished. stdout is only locked once the state isn't,
+        // since a read may begin while the caller holds stdout's lock
+        let messages = std::mem::take(&mut state.messages);
+        drop(state);
+        let mut stdout = io::stdout().lock();
+        for msg in messages {
+            let _ = stdout.write_all(msg.as_bytes());
+        }
+    }
+}
+
+/// A handle for printing above a [`Prompt`][crate::Prompt] while it's reading input
+///
+/// Created with [`Prompt::external_printer`][crate::Prompt::external_printer]. It may be cloned and
+/// sent to other threads.
+///
+/// # Example
+///
+/// ```no_run
+/// let mut prompt = pomprt::new(">> ");
+/// let printer = prompt.external_printer();
+///
+/// std::thread::spawn(move || loop {
+///     std::thread::sleep(std::time::Duration::from_secs(1));
+///     printer.print("tick").unwrap();
+/// });
+///
+/// for input in prompt {
+///     println!("{input}");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ExternalPrinter {
+    shared: Arc<Shared>,
+}
+
+impl ExternalPrinter {
+    pub(crate) fn new(shared: Arc<Shared>) -> Self {
+        Self { shared }
+    }
+
+    /// Prints a message above the prompt, which is then redrawn below it. A newline is added to
+    /// the message if it doesn't end with one already.
+    ///
+    /// When reading from a terminal on Unix, or with
+    /// [`Prompt::read_async`][crate::Prompt::read_async], the message is printed right away.
+    /// Elsewhere, including [`Prompt::read_from`][crate::Prompt::read_from], it's only printed on
+    /// the next keypress. If no read is in progress, it's printed to stdout directly.
+    ///
+    /// # Errors
+    ///
+    /// Errors only if no read is in progress and writing to stdout fails.
+    pub fn print(&self, msg: impl Into<String>) -> io::Result<()> {
+        let mut msg = msg.into();
+        if !msg.ends_with('\n') {
+            msg.push('\n');
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.active {
+            drop(state);
+            return io::stdout().lock().write_all(msg.as_bytes());
+        }
+
+        state.messages.push(msg);
+        let waker = state.wake();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+/// A handle for cancelling a [`Prompt`][crate::Prompt]'s read
+///
+/// Created with [`Prompt::cancel_handle`][crate::Prompt::cancel_handle]. It may be cloned and sent
+/// to other threads.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::{thread, time::Duration};
+/// let mut prompt = pomprt::new(">> ");
+/// let cancel = prompt.cancel_handle();
+///
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(60));
+///     cancel.cancel();
+/// });
+///
+/// match prompt.read() {
+///     Err(pomprt::Error::Cancelled) => println!("too slow!"),
+///     input => println!("{input:?}"),
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CancelHandle {
+    shared: Arc<Shared>,
+}
+
+impl CancelHandle {
+    pub(crate) fn new(shared: Arc<Shared>) -> Self {
+        Self { shared }
+    }
+
+    /// Cancels the read in progress, making it return
+    /// [`Error::Cancelled`][crate::Error::Cancelled]. Does nothing if no read is in progress.
+    ///
+    /// When reading from a terminal on Unix, or with
+    /// [`Prompt::read_async`][crate::Prompt::read_async], the read returns right away. Elsewhere,
+    /// including [`Prompt::read_from`][crate::Prompt::read_from], it only does so on the next
+    /// keypress. Reads from stdin that isn't a terminal can't be cancelled.
+    pub fn cancel(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.active {
+            return;
+        }
+        state.cancelled = true;
+        let waker = state.wake();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
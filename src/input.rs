@@ -0,0 +1,71 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An asynchronous source of bytes, read by [`Prompt::read_async`][crate::Prompt::read_async]
+///
+/// This is the same as `AsyncRead` from `futures` or `tokio`, so wrapping their types only takes
+/// forwarding `poll_read`. For `tokio`, which reads into a `ReadBuf`:
+///
+/// ```ignore
+/// struct Stdin(tokio::io::Stdin);
+///
+/// impl pomprt::AsyncInput for Stdin {
+///     fn poll_read(
+///         mut self: Pin<&mut Self>,
+///         cx: &mut Context<'_>,
+///         buf: &mut [u8],
+///     ) -> Poll<io::Result<usize>> {
+///         let mut buf = tokio::io::ReadBuf::new(buf);
+///         ready!(Pin::new(&mut self.0).poll_read(cx, &mut buf))?;
+///         Poll::Ready(Ok(buf.filled().len()))
+///     }
+/// }
+/// ```
+pub trait AsyncInput {
+    /// Attempts to read into `buf`, returning how many bytes were read
+    ///
+    /// If no data is available, the current task should be woken once it is, returning
+    /// [`Poll::Pending`]. Reading 0 bytes means the end of input was reached.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+impl<T: AsyncInput + Unpin + ?Sized> AsyncInput for &mut T {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncInput + Unpin + ?Sized> AsyncInput for Box<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+/// Always ready, like its [`Read`][io::Read] implementation
+impl AsyncInput for &[u8] {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(io::Read::read(&mut *self, buf))
+    }
+}
@@ -0,0 +1,90 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use crate::ansi::{Ansi, Reader};
+use crate::prompt::RawMode;
+use crate::{sys, Error};
+
+const ESC: u8 = 0x1b;
+/// How long to wait after an ESC byte for the rest of a sequence, before taking it as Escape
+const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// A single keypress, read by [`read_key`]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    /// The key as an [`Ansi`] sequence. Escape on its own is `Ansi::Control(b'[')`
+    pub fn ansi(&self) -> Ansi<'_> {
+        if self.0 == [ESC] {
+            return Ansi::Control(b'[');
+        }
+        let mut r = Reader::new(&self.0[..]);
+        match r.read_sequence().expect("keys are complete sequences") {
+            Ansi::Char(c) => Ansi::Char(c),
+            Ansi::Control(c) => Ansi::Control(c),
+            Ansi::Esc(c) => Ansi::Esc(c),
+            Ansi::Csi(_) => Ansi::Csi(&self.0[2..]),
+        }
+    }
+
+    /// Raw bytes making up the key
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Reads a single keypress from stdin, in raw mode
+///
+/// # Example
+///
+/// ```no_run
+/// use pomprt::ansi::Ansi;
+///
+/// println!("[a]dd, [r]emove or [q]uit?");
+/// match pomprt::read_key()?.ansi() {
+///     Ansi::Char('a') => println!("adding"),
+///     Ansi::Char('r') => println!("removing"),
+///     _ => println!("bye"),
+/// }
+/// # Ok::<(), pomprt::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns [`Error::Interrupt`] for Ctrl-C. Other errors might occur: see [`Error`]
+pub fn read_key() -> Result<Key, Error> {
+    let _raw = RawMode::acquire();
+    let mut input = sys::stdin();
+    let mut first = [0];
+    input.read_exact(&mut first)?;
+    // Escape is only told apart from the start of a sequence by nothing following it
+    if first == [ESC] && sys::can_wait() && !follows(ESC_TIMEOUT)? {
+        return Ok(Key(vec![ESC]));
+    }
+
+    let mut r = Reader::new((&first[..]).chain(input));
+    if r.read_sequence()? == Ansi::Control(b'C') {
+        return Err(Error::Interrupt);
+    }
+
+    Ok(Key(r.raw().to_owned()))
+}
+
+/// Returns `true` if more input arrives within `timeout`
+fn follows(timeout: Duration) -> Result<bool, Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match sys::wait(Some(left))? {
+            sys::Wake::Input => return Ok(true),
+            sys::Wake::Signal => {}
+            sys::Wake::Timeout => return Ok(false),
+        }
+    }
+}
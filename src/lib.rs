@@ -51,20 +51,32 @@
 
 pub mod ansi;
 mod editor;
+mod handle;
+mod input;
+mod key;
 mod prompt;
+mod render;
+mod state;
+mod sys;
+mod term;
+mod widget;
 
-pub use editor::{Basic, Completion, Editor, Event};
-pub use prompt::{Error, Prompt};
+pub use editor::{Basic, Completion, Editor, Event, Validation};
+pub use handle::{CancelHandle, ExternalPrinter};
+pub use input::AsyncInput;
+pub use key::{read_key, Key};
+pub use prompt::{Echo, Error, Prompt, RawMode, Transcript};
+pub use state::{PromptState, Status};
 
 pub use Error::{Eof, Interrupt};
 
 /// Construct a new [`Prompt`] with the default editor
-pub const fn new(prompt: &str) -> Prompt {
+pub const fn new(prompt: &str) -> Prompt<'_> {
     Prompt::new(prompt)
 }
 
 /// Construct a new [`Prompt`] with a custom editor
-pub const fn with<E: Editor>(editor: E, prompt: &str) -> Prompt<E> {
+pub const fn with<E: Editor>(editor: E, prompt: &str) -> Prompt<'_, E> {
     Prompt::with(editor, prompt)
 }
 
@@ -3,10 +3,19 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::borrow::Cow;
+use std::future;
 use std::io::{self, IsTerminal, Read, Write};
+use std::panic;
+use std::pin::Pin;
+use std::sync::{Arc, Once, OnceLock};
+use std::task::Poll;
+use std::time::{Duration, Instant};
 
-use crate::{ansi, Basic};
-use crate::{Completion, Editor, Event};
+use crate::ansi::{self, Ansi};
+use crate::handle::{CancelHandle, ExternalPrinter, Shared};
+use crate::state::{self, PromptState, Status};
+use crate::{sys, term, AsyncInput, Basic, Editor, Validation};
 
 /// Error returned by [`Prompt::read`]
 #[derive(Debug)]
@@ -16,6 +25,11 @@ pub enum Error {
     Eof,
     /// Interrupt signal (ctrl-c)
     Interrupt,
+    /// The read was cancelled through a [`CancelHandle`]
+    Cancelled,
+    /// No key was pressed in time, see [`Prompt::read_timeout`]. Contains the input so far, unless
+    /// it's [secret](Echo::is_secret)
+    Timeout(String),
     /// Error ocurred during read/write
     Io(io::Error),
 }
@@ -27,6 +41,8 @@ impl std::fmt::Display for Error {
         match self {
             Self::Eof => write!(f, "eof reached"),
             Self::Interrupt => write!(f, "interrupt"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Timeout(_) => write!(f, "timed out"),
             Self::Io(e) => write!(f, "{e}"),
         }
     }
@@ -38,11 +54,45 @@ impl From<io::Error> for Error {
     }
 }
 
-struct CompletionState {
-    range: std::ops::Range<usize>,
-    results: Vec<String>,
-    current: usize,
-    buffer: String,
+/// How input is shown on screen, see [`Prompt::set_echo`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Echo {
+    /// Input is shown as typed, with highlighting
+    #[default]
+    Normal,
+    /// Every character is shown as the given one, usually `*`
+    Mask(char),
+    /// Nothing is shown
+    Hidden,
+}
+
+impl Echo {
+    /// Returns `true` if input is kept secret, i.e. anything but [`Echo::Normal`]
+    pub fn is_secret(self) -> bool {
+        self != Self::Normal
+    }
+
+    /// Replaces `text` with what should be shown for it
+    pub(crate) fn mask(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Normal => Cow::Borrowed(text),
+            Self::Mask(mask) => Cow::Owned(text.chars().map(|_| mask).collect()),
+            Self::Hidden => Cow::Borrowed(""),
+        }
+    }
+}
+
+/// What's printed when stdin is not a terminal, see [`Prompt::set_transcript`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Transcript {
+    /// Nothing is printed
+    #[default]
+    Off,
+    /// The prompt and every line read are printed as plain text
+    Plain,
+    /// Like [`Transcript::Plain`], but highlighted by the [`Editor`] unless
+    /// [`NO_COLOR`](https://no-color.org) is set
+    Highlighted,
 }
 
 /// The pomprt prompt
@@ -50,12 +100,20 @@ struct CompletionState {
 /// See the [crate's documentation](crate) for more details
 #[must_use]
 pub struct Prompt<'a, E: Editor = Basic> {
-    prompt: &'a str,
-    multiline: &'a str,
+    pub(crate) prompt: Cow<'a, str>,
+    pub(crate) multiline: Cow<'a, str>,
+    pub(crate) wrap: bool,
+    pub(crate) accessible: bool,
+    pub(crate) echo: Echo,
+    pub(crate) transcript: Transcript,
+    pub(crate) placeholder: Cow<'a, str>,
+    pub(crate) max_len: Option<usize>,
+    pub(crate) filter: Option<Box<dyn Fn(char) -> bool + Send + Sync + 'a>>,
     /// The current [editor][Editor]
     pub editor: E,
     /// Input history. Entries are added automatically by [`Prompt::read`]
     pub history: Vec<String>,
+    shared: OnceLock<Arc<Shared>>,
 }
 
 impl<'a> Prompt<'a> {
@@ -74,314 +132,721 @@ impl<'a, E: Editor> Prompt<'a, E> {
     /// Construct a new multiline prompt with a given editor
     pub const fn with_multiline(editor: E, prompt: &'a str, multiline: &'a str) -> Self {
         Self {
-            prompt,
-            multiline,
+            prompt: Cow::Borrowed(prompt),
+            multiline: Cow::Borrowed(multiline),
+            wrap: true,
+            accessible: false,
+            echo: Echo::Normal,
+            transcript: Transcript::Off,
+            placeholder: Cow::Borrowed(""),
+            max_len: None,
+            filter: None,
             editor,
             history: Vec::new(),
+            shared: OnceLock::new(),
         }
     }
 
-    /// Set the current prompt
-    pub fn set_prompt(&mut self, prompt: &'a str) {
-        self.prompt = prompt;
+    /// Set the current prompt, either borrowed or owned
+    ///
+    /// ```
+    /// let mut prompt = pomprt::new("% ");
+    /// let code = 1;
+    /// prompt.set_prompt(format!("[{code}] % "));
+    /// ```
+    pub fn set_prompt(&mut self, prompt: impl Into<Cow<'a, str>>) {
+        self.prompt = prompt.into();
     }
 
     /// Set the current multiline prompt
-    pub fn set_multiline(&mut self, prompt: &'a str) {
-        self.multiline = prompt;
+    pub fn set_multiline(&mut self, prompt: impl Into<Cow<'a, str>>) {
+        self.multiline = prompt.into();
+    }
+
+    /// Set whether long lines wrap (the default), or scroll horizontally instead
+    ///
+    /// When not wrapping, `<` and `>` are shown where a line is cut off. The whole input is still
+    /// shown once submitted.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Set whether to draw the prompt for screen readers
+    ///
+    /// Instead of redrawing the input, only what's inserted or deleted is written, and the cursor
+    /// is moved in place. Input isn't highlighted, hints and placeholders aren't shown, and long
+    /// lines always wrap. Completion candidates and messages from [`Editor::validate`] are
+    /// printed as plain lines below the input, which is then shown again after them.
+    ///
+    /// ```
+    /// let mut prompt = pomprt::new(">> ");
+    /// prompt.set_accessible(true);
+    /// ```
+    pub fn set_accessible(&mut self, accessible: bool) {
+        self.accessible = accessible;
+    }
+
+    /// Set how input is shown. Anything but [`Echo::Normal`] makes it secret, for passwords
+    ///
+    /// Secret input is never added to [`history`](Prompt::history), can't span multiple lines,
+    /// and has no hints or completion. Memory used for it while reading is zeroed afterwards,
+    /// though clearing the returned string is left to the caller.
+    ///
+    /// ```no_run
+    /// use pomprt::Echo;
+    ///
+    /// let mut prompt = pomprt::new("password: ");
+    /// prompt.set_echo(Echo::Mask('*'));
+    /// let password = prompt.read()?;
+    /// # Ok::<(), pomprt::Error>(())
+    /// ```
+    pub fn set_echo(&mut self, echo: Echo) {
+        self.echo = echo;
+    }
+
+    /// Set whether piped input is printed to stdout along with the prompt, like it would be shown
+    /// on a terminal
+    ///
+    /// This is useful for logging scripted sessions. Secret input is still masked, see
+    /// [`set_echo`](Prompt::set_echo).
+    ///
+    /// ```
+    /// use pomprt::Transcript;
+    ///
+    /// let mut prompt = pomprt::new(">> ");
+    /// prompt.set_transcript(Transcript::Plain);
+    /// ```
+    pub fn set_transcript(&mut self, transcript: Transcript) {
+        self.transcript = transcript;
+    }
+
+    /// Set text to show while the input is empty, or an empty string for none
+    ///
+    /// It's dimmed by default, see [`Editor::highlight_placeholder`].
+    ///
+    /// ```
+    /// let mut prompt = pomprt::new("search: ");
+    /// prompt.set_placeholder("type a query, Tab to complete");
+    /// ```
+    pub fn set_placeholder(&mut self, text: impl Into<Cow<'a, str>>) {
+        self.placeholder = text.into();
+    }
+
+    /// Set the maximum length of input, in grapheme clusters
+    ///
+    /// Typing, pasting or completing past it is rejected with a bell. Graphemes are counted
+    /// roughly, without the full Unicode rules: accents, emoji sequences and flags are handled,
+    /// but Hangul syllables spelled out in jamo or Indic conjuncts count as several.
+    pub fn set_max_len(&mut self, len: Option<usize>) {
+        self.max_len = len;
+    }
+
+    /// Set which characters are allowed in input
+    ///
+    /// Typing, pasting or completing anything else is rejected with a bell. The filter may
+    /// capture its environment, for instance to allow characters read from a config file.
+    ///
+    /// ```
+    /// let mut prompt = pomprt::new("pin: ");
+    /// prompt.set_filter(|c| c.is_ascii_digit());
+    /// prompt.set_max_len(Some(6));
+    ///
+    /// let allowed = String::from("0123456789abcdef");
+    /// let mut prompt = pomprt::new("hex: ");
+    /// prompt.set_filter(move |c| allowed.contains(c));
+    /// ```
+    pub fn set_filter(&mut self, filter: impl Fn(char) -> bool + Send + Sync + 'a) {
+        self.filter = Some(Box::new(filter));
+    }
+
+    /// Create a handle for printing messages above the prompt from other threads
+    ///
+    /// See [`ExternalPrinter`]
+    pub fn external_printer(&self) -> ExternalPrinter {
+        ExternalPrinter::new(Arc::clone(self.shared()))
+    }
+
+    /// Create a handle for cancelling reads from other threads
+    ///
+    /// See [`CancelHandle`]
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle::new(Arc::clone(self.shared()))
+    }
+
+    fn shared(&self) -> &Arc<Shared> {
+        self.shared.get_or_init(Default::default)
     }
 
     /// Start the prompt and read user input
     ///
     /// By default, it will use regular stdin and stdout. If stdin is not a terminal, no prompt
-    /// will be shown. If stdout is not a terminal, it will instead fall back to stderr. See
-    /// [`read_from`](Prompt::read_from) for specifying i/o.
+    /// will be shown unless set with [`set_transcript`](Prompt::set_transcript), and lines are
+    /// read for as long as [`Editor::is_multiline`] says the input continues. If stdout is not a
+    /// terminal, it will instead fall back to stderr. See [`read_from`](Prompt::read_from) for
+    /// specifying i/o.
+    ///
+    /// Terminal input is read in chunks. Anything past the end of the input, such as the rest of a
+    /// pasted block of lines, is kept for the next read rather than left in [`io::stdin`], so it
+    /// should be read through the prompt as well.
+    ///
+    /// On dumb terminals (`TERM=dumb`), the prompt is printed and a line is read without any
+    /// escape sequences, leaving editing up to the terminal. Colors from the [`Editor`] are left
+    /// out if [`NO_COLOR`](https://no-color.org) is set.
     ///
     /// # Errors
     ///
-    /// May return [`Error::Eof`] or [`Error::Interrupt`] on user input. Other errors might occur:
-    /// see [`Error`]
+    /// May return [`Error::Eof`] or [`Error::Interrupt`] on user input, or [`Error::Cancelled`] if
+    /// cancelled through a [`CancelHandle`]. Other errors might occur: see [`Error`]
     pub fn read(&mut self) -> Result<String, Error> {
+        self.read_stdin("", 0, None)
+    }
+
+    /// Start the prompt and read user input, giving up if no key is pressed for `timeout`
+    ///
+    /// The timer starts over with every keypress, or with every line on dumb terminals, where the
+    /// line being typed isn't part of the input so far. Timeouts only apply to terminals on Unix:
+    /// see [`read`](Prompt::read). Elsewhere, `timeout` is ignored and this is the same as `read`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] with the input so far if the timeout elapses. Otherwise, see
+    /// [`read`](Prompt::read)
+    pub fn read_timeout(&mut self, timeout: Duration) -> Result<String, Error> {
+        self.read_stdin("", 0, Some(timeout))
+    }
+
+    /// Start the prompt and read user input, starting with `text` already typed in
+    ///
+    /// The cursor is placed at byte offset `cursor` into `text`, or at the end if it's past it. If
+    /// stdin is not a terminal, or on dumb terminals, where input can't be edited, `text` is
+    /// ignored: see [`read`](Prompt::read).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let mut prompt = pomprt::new("rename to: ");
+    /// // place the cursor before the extension
+    /// let name = prompt.read_with_initial("notes.txt", 5)?;
+    /// # Ok::<(), pomprt::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`read`](Prompt::read)
+    pub fn read_with_initial(&mut self, text: &str, cursor: usize) -> Result<String, Error> {
+        self.read_stdin(text, cursor, None)
+    }
+
+    fn read_stdin(
+        &mut self,
+        text: &str,
+        cursor: usize,
+        timeout: Option<Duration>,
+    ) -> Result<String, Error> {
         if !io::stdin().is_terminal() {
-            let mut buffer = String::with_capacity(128);
-            if io::stdin().read_line(&mut buffer)? == 0 {
-                return Err(Error::Eof);
-            }
-            return Ok(buffer);
+            return match self.transcript {
+                Transcript::Off => self.read_lines(io::sink(), false, None),
+                _ => self.read_lines(io::stdout().lock(), false, None),
+            };
+        }
+
+        if term::is_dumb() {
+            return if io::stdout().is_terminal() {
+                self.read_lines(io::stdout().lock(), true, timeout)
+            } else {
+                self.read_lines(io::stderr().lock(), true, timeout)
+            };
         }
 
         if io::stdout().is_terminal() {
-            self.read_from(io::stdin().lock(), io::stdout().lock())
+            self.read_inner(
+                sys::stdin(),
+                io::stdout().lock(),
+                true,
+                (text, cursor),
+                timeout,
+            )
         } else {
-            self.read_from(io::stdin().lock(), io::stderr().lock())
+            self.read_inner(
+                sys::stdin(),
+                io::stderr().lock(),
+                true,
+                (text, cursor),
+                timeout,
+            )
         }
     }
 
     /// Start the prompt and read user input, specifying stdin/stdout.
     ///
+    /// Unlike [`read`](Prompt::read), terminal resizes are only picked up on the next keypress,
+    /// since `input` can't be waited on.
+    ///
     /// # Errors
     ///
     /// See [`read`][Prompt::read]
     pub fn read_from(&mut self, input: impl Read, output: impl Write) -> Result<String, Error> {
-        let mut buffer = String::with_capacity(128);
-
-        let raw = RawMode::acquire();
-        let mut r = ansi::Reader::new(input);
-        let mut w = io::BufWriter::new(output);
-
-        let mut history_entry = self.history.len();
-        let mut saved_entry = String::new();
-        let mut cursor = 0;
-        let mut completion = None;
+        self.read_inner(input, output, false, ("", 0), None)
+    }
 
-        write!(w, "{}", self.editor.highlight_prompt(self.prompt, false))?;
-        w.flush()?;
+    /// Reads whole lines, for non-terminal input and terminals that don't understand escape
+    /// sequences, leaving editing up to the terminal itself
+    ///
+    /// Lines are read until [`Editor::is_multiline`] is `false`. Secret input isn't shown at all
+    /// on terminals. Input is only checked with [`Editor::validate`] on terminals, since it can't
+    /// be fixed otherwise. Off terminals, lines are echoed back for transcripts.
+    ///
+    /// On terminals, the timeout starts over with every line, and the read may be cancelled.
+    fn read_lines(
+        &mut self,
+        mut w: impl Write,
+        tty: bool,
+        timeout: Option<Duration>,
+    ) -> Result<String, Error> {
+        let secret = tty && self.echo.is_secret();
+        let color = !tty && self.transcript == Transcript::Highlighted && !term::no_color();
+        let shared = Arc::clone(self.shared());
+        // piped input isn't waited on, so there's no way to interrupt it
+        let _active = tty.then(|| shared.begin(true));
+        // lines are read in cooked mode, where Ctrl-C sends SIGINT instead
+        let _signals = tty.then(sys::Signals::interrupt);
+        let mut buffer = String::with_capacity(128);
+        let mut multiline = false;
 
         loop {
-            let cur_completion = completion.take();
-            let width = match rawrrr::get_size() {
-                Some((w, _)) if w > 0 => w,
-                _ => 80,
+            let prompt = if multiline {
+                &self.multiline
+            } else {
+                &self.prompt
             };
-            let mut written = 0;
-            match self.editor.next_event(&mut r)? {
-                Event::Insert(c) => {
-                    self.editor.insert(&mut buffer, &mut cursor, c);
-                    written += self.redraw(&mut w, &buffer, width)?;
-                }
-                Event::Enter if self.editor.is_multiline(&buffer, cursor) => {
-                    self.editor.insert(&mut buffer, &mut cursor, '\n');
-                    written += self.redraw(&mut w, &buffer, width)?;
-                }
-                Event::Enter => {
-                    if !self.history.last().is_some_and(|e| e.eq(&buffer)) {
-                        self.history.push(buffer.clone());
-                    }
-                    self.display_buffer(&mut w, &buffer)?;
+            let prompt = self.editor.highlight_prompt(prompt, multiline);
+            let prompt = match color {
+                true => format!("{prompt}\x1b[m"),
+                false => term::strip_sgr(&prompt),
+            };
+            write!(w, "{prompt}")?;
+            w.flush()?;
+
+            let start = buffer.len();
+            let deadline = timeout.map(|t| Instant::now() + t);
+            let read = if secret {
+                read_secret_line(&mut buffer, || {
+                    wait_line(&shared, deadline, &mut w, &prompt)
+                })
+                .map(|()| true)
+            } else if tty {
+                wait_line(&shared, deadline, &mut w, &prompt)
+                    .and_then(|()| Ok(read_line(sys::stdin(), &mut buffer)? > 0))
+            } else {
+                Ok(io::stdin().read_line(&mut buffer)? > 0)
+            };
+            match read {
+                Ok(true) if secret => writeln!(w)?,
+                Ok(true) => {}
+                Ok(false) => {
                     writeln!(w)?;
-                    w.flush()?;
-                    return Ok(buffer);
+                    if buffer.is_empty() {
+                        return Err(Error::Eof);
+                    }
+                    // whatever was left incomplete
+                    buffer.pop();
+                    break;
                 }
-                Event::Backspace if cursor > 0 => loop {
-                    cursor -= 1;
-                    if buffer.is_char_boundary(cursor) {
-                        buffer.remove(cursor);
-                        written += self.redraw(&mut w, &buffer, width)?;
-                        break;
+                Err(e) => {
+                    writeln!(w)?;
+                    if secret {
+                        state::zero_string(std::mem::take(&mut buffer));
                     }
-                },
-                Event::Tab => {
-                    completion = cur_completion.or_else(|| {
-                        self.editor
-                            .complete(&buffer, cursor)
-                            .map(|Completion(range, results)| CompletionState {
-                                range,
-                                results,
-                                current: 0,
-                                buffer: buffer.clone(),
-                            })
+                    return Err(match e {
+                        Error::Timeout(_) => Error::Timeout(buffer),
+                        e => e,
                     });
+                }
+            }
+            buffer.truncate(buffer.trim_end_matches(['\r', '\n']).len());
+            if !tty {
+                let line = self.echo.mask(&buffer[start..]);
+                if color && !self.echo.is_secret() {
+                    writeln!(w, "{}\x1b[m", self.editor.highlight(&line))?;
+                } else {
+                    writeln!(w, "{line}")?;
+                }
+            }
 
-                    match completion.as_mut() {
-                        Some(c) if c.results.is_empty() => continue,
-                        // automatically submit if only one entry is present
-                        Some(c) if c.results.len() == 1 => {
-                            buffer.replace_range(c.range.clone(), &c.results[0]);
-                            cursor = c.range.start + c.results[0].len();
-                            completion = None;
-                        }
-                        Some(c) => {
-                            buffer.clone_from(&c.buffer);
-                            buffer.replace_range(c.range.clone(), &c.results[c.current]);
-                            cursor = c.range.start + c.results[c.current].len();
-                            c.current = (c.current + 1) % c.results.len();
+            multiline = !secret && self.editor.is_multiline(&buffer, buffer.len());
+            if !multiline {
+                match self.editor.validate(&buffer) {
+                    Validation::Valid => break,
+                    Validation::Invalid(_) if !tty => break,
+                    Validation::Incomplete => multiline = !secret,
+                    Validation::Invalid(msg) => {
+                        writeln!(w, "{}", term::strip_sgr(&self.editor.highlight_hint(&msg)))?;
+                        if secret {
+                            state::zero_string(std::mem::take(&mut buffer));
                         }
-                        None => self.editor.indent(&mut buffer, &mut cursor),
+                        buffer.clear();
+                        continue;
                     }
+                }
+            }
+            if multiline {
+                buffer.push('\n');
+            } else {
+                buffer.clear();
+            }
+        }
+
+        if tty && !secret && !self.history.last().is_some_and(|e| e.eq(&buffer)) {
+            self.history.push(buffer.clone());
+        }
+        Ok(buffer)
+    }
 
-                    written += self.redraw(&mut w, &buffer, width)?;
+    /// Start the prompt and read user input asynchronously, from `input` and to `output`
+    ///
+    /// This doesn't depend on any runtime: `input` only needs to implement [`AsyncInput`], which is
+    /// easily done for types from `tokio` or `futures`. Writing to `output` is assumed to be fast,
+    /// as it is for terminals. Like [`read_from`](Prompt::read_from), terminal resizes are only
+    /// picked up on the next keypress.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::{io, pin::Pin, task::{Context, Poll}};
+    ///
+    /// struct Stdin; // say, a wrapper around your runtime's stdin
+    ///
+    /// impl pomprt::AsyncInput for Stdin {
+    ///     fn poll_read(
+    ///         self: Pin<&mut Self>,
+    ///         cx: &mut Context<'_>,
+    ///         buf: &mut [u8],
+    ///     ) -> Poll<io::Result<usize>> {
+    ///         todo!()
+    ///     }
+    /// }
+    ///
+    /// # async fn run() -> Result<(), pomprt::Error> {
+    /// let mut prompt = pomprt::new(">> ");
+    /// let input = prompt.read_async(Stdin, io::stdout()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`read`][Prompt::read]
+    pub async fn read_async(
+        &mut self,
+        mut input: impl AsyncInput + Unpin,
+        output: impl Write,
+    ) -> Result<String, Error> {
+        let _raw = RawMode::acquire();
+        let shared = Arc::clone(self.shared());
+        let _active = shared.begin(false);
+        let mut w = io::BufWriter::new(output);
+        let secret = self.echo.is_secret();
+        let mut state = PromptState::new(self, false);
+
+        state.redraw(&mut w)?;
+        w.flush()?;
+
+        // like `read_from`, leftover input would be lost
+        let mut byte = [0];
+        loop {
+            let read = future::poll_fn(|cx| {
+                shared.register(cx.waker());
+                print_messages(&shared, &mut state, &mut w)?;
+                if shared.take_cancel() {
+                    return Poll::Ready(Err(Error::Cancelled));
                 }
-                Event::Left if cursor > 0 => loop {
-                    cursor -= 1;
-                    if buffer.is_char_boundary(cursor) {
-                        break;
-                    }
-                },
-                Event::Right if cursor < buffer.len() => loop {
-                    cursor += 1;
-                    if buffer.is_char_boundary(cursor) {
-                        break;
+                Pin::new(&mut input)
+                    .poll_read(cx, &mut byte)
+                    .map_err(Error::Io)
+            })
+            .await;
+
+            let status = match read {
+                Ok(0) => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Ok(_) => {
+                    let status = state.feed(&byte, &mut w)?;
+                    if secret {
+                        state::zero(&mut byte);
                     }
-                },
-                Event::Home => cursor = 0,
-                Event::End => cursor = buffer.len(),
-                Event::Interrupt if buffer.is_empty() => {
-                    self.display_buffer(&mut w, &buffer)?;
-                    writeln!(w)?;
-                    return Err(Error::Interrupt);
-                }
-                Event::Eof if buffer.is_empty() => {
-                    self.display_buffer(&mut w, &buffer)?;
-                    writeln!(w)?;
-                    return Err(Error::Eof);
-                }
-                Event::Interrupt => {
-                    self.display_buffer(&mut w, &buffer)?;
-                    writeln!(w)?;
-                    cursor = 0;
-                    buffer.clear();
-                    written += self.redraw(&mut w, &buffer, width)?;
-                }
-                #[cfg(all(unix, feature = "suspend"))]
-                Event::Suspend => unsafe {
-                    // SIGTSTP is what usually happens -- the process gets put in the background
-                    libc::kill(std::process::id() as i32, libc::SIGTSTP);
-                    // once we're back, we need to put the tty in raw mode again
-                    rawrrr::enable_raw();
-                    written += self.redraw(&mut w, &buffer, width)?;
-                },
-                #[cfg(feature = "abort")]
-                Event::Abort => {
-                    drop(raw);
-                    std::process::abort()
+                    status
                 }
-                Event::Up if history_entry > 0 => {
-                    if history_entry == self.history.len() {
-                        saved_entry = buffer;
-                    }
-                    history_entry -= 1;
-                    buffer = self
-                        .history
-                        .get(history_entry)
-                        .unwrap_or(&saved_entry)
-                        .clone();
-                    cursor = buffer.len();
-                    written += self.redraw(&mut w, &buffer, width)?;
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(Error::Cancelled) => {
+                    state.abort(&mut w)?;
+                    w.flush()?;
+                    return Err(Error::Cancelled);
                 }
-                Event::Down if history_entry < self.history.len() => {
-                    history_entry += 1;
-                    buffer = self
-                        .history
-                        .get(history_entry)
-                        .unwrap_or(&saved_entry)
-                        .clone();
-                    cursor = buffer.len();
-                    written += self.redraw(&mut w, &buffer, width)?;
+                Err(e) => return Err(e),
+            };
+            w.flush()?;
+            match status {
+                Status::Pending => {}
+                Status::Submitted(input) => return Ok(input),
+                Status::Interrupted => return Err(Error::Interrupt),
+                Status::Eof => return Err(Error::Eof),
+            }
+        }
+    }
+
+    fn read_inner(
+        &mut self,
+        mut input: impl Read,
+        output: impl Write,
+        tty: bool,
+        (text, cursor): (&str, usize),
+        timeout: Option<Duration>,
+    ) -> Result<String, Error> {
+        let _raw = RawMode::acquire();
+        let _signals = tty.then(sys::Signals::install);
+        let shared = Arc::clone(self.shared());
+        let _active = shared.begin(tty);
+        let mut w = io::BufWriter::new(output);
+        let sync = tty && term::sync_output(&mut w)?;
+        if tty {
+            term::line_start(&mut w)?;
+        }
+        let secret = self.echo.is_secret();
+        let mut state = PromptState::new(self, sync);
+        state.set_buffer(text, cursor);
+
+        state.redraw(&mut w)?;
+        w.flush()?;
+
+        // what's left over from a terminal is kept for the next read, and not seen by `io::stdin`.
+        // other inputs can't be given it back, so read them a byte at a time
+        let mut chunk = [0; 1024];
+        let len = if tty { chunk.len() } else { 1 };
+
+        let result = 'read: loop {
+            print_messages(&shared, &mut state, &mut w)?;
+            let deadline = timeout.map(|t| Instant::now() + t);
+            let cancelled = loop {
+                if shared.take_cancel() {
+                    break true;
                 }
-                Event::Clear => {
-                    write!(w, "\x1b[H\x1b[2J")?;
-                    written += self.redraw(&mut w, &buffer, width)?;
+                if !tty {
+                    break false;
                 }
-                Event::LeftWord => {
-                    while cursor > 0 {
-                        cursor -= 1;
-                        if !buffer[..cursor].ends_with(E::is_keyword) {
-                            break;
-                        }
+                let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                match sys::wait(left)? {
+                    sys::Wake::Input => break false,
+                    sys::Wake::Signal => {}
+                    sys::Wake::Timeout => {
+                        let buffer = state.abort(&mut w)?;
+                        w.flush()?;
+                        break 'read Err(Error::Timeout(buffer));
                     }
                 }
-                Event::RightWord => {
-                    while cursor < buffer.len() {
-                        cursor += 1;
-                        if !buffer[cursor..].starts_with(E::is_keyword) {
-                            break;
-                        }
-                    }
+                if sys::take_continue() {
+                    state.resume(&mut w)?;
+                    w.flush()?;
+                } else if sys::take_resize() {
+                    state.redraw(&mut w)?;
+                    w.flush()?;
                 }
-                _ => continue,
+                print_messages(&shared, &mut state, &mut w)?;
+            };
+            if cancelled {
+                state.abort(&mut w)?;
+                w.flush()?;
+                break Err(Error::Cancelled);
             }
 
-            let mut col = 0;
-            let line = count_lines(
-                self.buf_lengths(&buffer[..cursor])
-                    .inspect(|len| col = len % width),
-                width,
-            );
-
-            if line > written {
-                write!(w, "{}", "\n".repeat(line - written))?;
-            } else if line != written {
-                write!(w, "\x1b[{}A", written - line)?;
-            }
+            let n = match input.read(&mut chunk[..len]) {
+                Ok(0) => break Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(Error::Io(e)),
+            };
 
-            write!(w, "\r")?;
-            if col != 0 {
-                write!(w, "\x1b[{col}C")?;
+            let status = state.feed(&chunk[..n], &mut w)?;
+            if secret {
+                state::zero(&mut chunk[..n]);
             }
             w.flush()?;
-            if line != 0 {
-                write!(w, "\x1b[{line}A")?; // defer moving back cursor to next redraw
+            match status {
+                Status::Pending => {}
+                Status::Submitted(input) => break Ok(input),
+                Status::Interrupted => break Err(Error::Interrupt),
+                Status::Eof => break Err(Error::Eof),
             }
+        };
+
+        if tty {
+            sys::unread(state.leftover());
         }
+        result
     }
+}
 
-    fn display_buffer(&self, w: &mut impl Write, buf: &str) -> io::Result<()> {
-        write!(w, "\r\x1b[J")?;
+/// Iterates through [`Prompt::read`], until either [`Error::Eof`], [`Error::Interrupt`] or
+/// [`Error::Cancelled`] is reached. Panics on I/O errors.
+impl<E: Editor> Iterator for Prompt<'_, E> {
+    type Item = String;
 
-        let hl = self.editor.highlight(buf) + " ";
-        let prompt = self.editor.highlight_prompt(self.prompt, false);
-        let multiline = self.editor.highlight_prompt(self.multiline, true);
-        let mut cur_prompt = &prompt;
-        for line in hl.split_inclusive('\n') {
-            write!(w, "{cur_prompt}\x1b[m{line}\x1b[m")?;
-            cur_prompt = &multiline;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read() {
+            Err(Error::Eof | Error::Interrupt | Error::Cancelled) => None,
+            r => Some(r.unwrap()),
         }
-
-        Ok(())
     }
+}
 
-    fn redraw(&self, w: &mut impl Write, buf: &str, width: usize) -> io::Result<usize> {
-        self.display_buffer(w, buf)?;
-        let mut lines = count_lines(self.buf_lengths(buf), width);
-        if let Some(hint) = self.editor.hint(buf) {
-            write!(w, "\n{}\x1b[m", self.editor.highlight_hint(&hint))?;
-            lines += count_lines(hint.split('\n').map(|line| line.chars().count()), width) + 1;
+/// Reads a line without echoing it, leaving no copies of it behind other than `buffer`. `wait` is
+/// called before every keypress
+fn read_secret_line(
+    buffer: &mut String,
+    mut wait: impl FnMut() -> Result<(), Error>,
+) -> Result<(), Error> {
+    let _raw = RawMode::acquire();
+    let mut r = ansi::Reader::new(sys::stdin());
+    loop {
+        wait()?;
+        let result = match r.read_sequence()? {
+            Ansi::Char(c) => {
+                state::reserve_secret(buffer);
+                buffer.push(c);
+                None
+            }
+            Ansi::Control(b'M' | b'J') => Some(Ok(())),
+            Ansi::Control(b'?' | b'H') => {
+                buffer.pop();
+                // the removed char is still past the end
+                let mut bytes = std::mem::take(buffer).into_bytes();
+                state::zero_spare(&mut bytes);
+                *buffer = String::from_utf8(bytes).expect("only spare capacity was changed");
+                None
+            }
+            Ansi::Control(b'C') => Some(Err(Error::Interrupt)),
+            Ansi::Control(b'D') if buffer.is_empty() => Some(Err(Error::Eof)),
+            _ => None,
+        };
+        r.wipe();
+        if let Some(result) = result {
+            return result;
         }
-
-        Ok(lines)
     }
+}
 
-    fn buf_lengths(&self, buf: &'a str) -> impl Iterator<Item = usize> + 'a {
-        let prompt = self.prompt.chars().count();
-        let multiline = self.multiline.chars().count();
-        let mut cur_prompt = prompt;
-        buf.split('\n').map(move |line| {
-            let len = cur_prompt + line.chars().count();
-            cur_prompt = multiline;
-            len
-        })
+/// Reads a line into `buffer` like [`BufRead::read_line`](io::BufRead::read_line), without reading
+/// anything past it, so that [`sys::wait`] still sees the next one
+fn read_line(input: impl Read, buffer: &mut String) -> io::Result<usize> {
+    let mut line = Vec::new();
+    // buffering is exactly what this avoids
+    #[allow(clippy::unbuffered_bytes)]
+    for byte in input.bytes() {
+        let byte = byte?;
+        line.push(byte);
+        if byte == b'\n' {
+            break;
+        }
     }
+
+    let line = String::from_utf8(line).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })?;
+    buffer.push_str(&line);
+    Ok(line.len())
 }
 
-/// Iterates through [`Prompt::read`], until either [`Error::Eof`] or [`Error::Interrupt`] is
-/// reached. Panics on I/O errors.
-impl<E: Editor> Iterator for Prompt<'_, E> {
-    type Item = String;
+/// Waits for input on a dumb terminal, printing messages from [`ExternalPrinter`]s meanwhile
+///
+/// Errors with [`Error::Timeout`], without any input, if `deadline` passes first.
+fn wait_line(
+    shared: &Shared,
+    deadline: Option<Instant>,
+    w: &mut impl Write,
+    prompt: &str,
+) -> Result<(), Error> {
+    loop {
+        let messages = shared.take_messages();
+        if !messages.is_empty() {
+            // whatever was typed is still there, though it's no longer shown
+            write!(w, "\n{}{prompt}", messages.concat())?;
+            w.flush()?;
+        }
+        if shared.take_cancel() {
+            return Err(Error::Cancelled);
+        }
+        if sys::take_interrupt() {
+            return Err(Error::Interrupt);
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.read() {
-            Err(Error::Eof | Error::Interrupt) => None,
-            r => Some(r.unwrap()),
+        let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        match sys::wait(left)? {
+            sys::Wake::Input => return Ok(()),
+            sys::Wake::Signal => {}
+            sys::Wake::Timeout => return Err(Error::Timeout(String::new())),
         }
     }
 }
 
-fn count_lines(lengths: impl Iterator<Item = usize>, width: usize) -> usize {
-    lengths.map(|x| x / width + 1).sum::<usize>() - 1
+/// Prints messages from [`ExternalPrinter`]s above the prompt
+fn print_messages<E: Editor>(
+    shared: &Shared,
+    state: &mut PromptState<'_, '_, E>,
+    w: &mut impl Write,
+) -> io::Result<()> {
+    let messages = shared.take_messages();
+    if messages.is_empty() {
+        return Ok(());
+    }
+
+    state.print_above(&messages.concat(), w)?;
+    w.flush()
 }
 
-struct RawMode;
+/// Keeps the terminal in raw mode until dropped
+///
+/// Reading through a [`Prompt`] or [`read_key`](crate::read_key) enters raw mode by itself, so
+/// this is only needed to drive a [`PromptState`] by hand. If the process panics or is killed by
+/// a signal meanwhile, the terminal is restored first.
+pub struct RawMode {
+    /// Whether the terminal was in raw mode already, in which case it's left that way
+    was_raw: bool,
+    _signals: sys::Signals,
+}
 
 impl RawMode {
-    fn acquire() -> Self {
-        rawrrr::enable_raw();
+    /// Puts the terminal in raw mode, if it isn't already
+    pub fn acquire() -> Self {
+        static HOOK: Once = Once::new();
+        // with `panic = "abort"`, any panic kills the process without running `Drop`
+        HOOK.call_once(|| {
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                if cfg!(panic = "abort") && rawrrr::is_raw() {
+                    sys::restore();
+                }
+                hook(info);
+            }));
+        });
+
+        // enabling it again would discard pending input
+        let was_raw = rawrrr::is_raw();
+        if !was_raw {
+            rawrrr::enable_raw();
+        }
 
-        Self
+        Self {
+            was_raw,
+            _signals: sys::Signals::terminate(),
+        }
     }
 }
 
 impl Drop for RawMode {
     fn drop(&mut self) {
-        rawrrr::disable_raw();
+        if !self.was_raw {
+            rawrrr::disable_raw();
+        }
     }
 }
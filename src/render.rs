@@ -0,0 +1,677 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Screen model used to only redraw what changed between keypresses
+
+use std::io::{self, Write};
+
+use crate::term;
+
+#[derive(Clone, Copy)]
+struct Cell {
+    c: char,
+    /// Index into [`Frame::styles`]
+    style: usize,
+}
+
+#[derive(Default)]
+struct Row {
+    cells: Vec<Cell>,
+    /// Whether this row continues on the next one
+    wrapped: bool,
+    /// Number of leading cells that stay in place when scrolling horizontally
+    fixed: Option<usize>,
+}
+
+/// A fully laid out screen, as it should look after drawing
+pub struct Frame {
+    width: usize,
+    height: usize,
+    /// Whether long rows wrap, or scroll horizontally
+    wrap: bool,
+    /// Whether printed SGR sequences are ignored, see [`term::no_color`]
+    plain: bool,
+    rows: Vec<Row>,
+    /// SGR sequences in effect for each cell. `styles[0]` is always the default style
+    styles: Vec<String>,
+    style: usize,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize, wrap: bool) -> Self {
+        Self {
+            width,
+            height,
+            wrap,
+            plain: term::no_color(),
+            rows: vec![Row::default()],
+            styles: vec![String::new()],
+            style: 0,
+        }
+    }
+
+    /// Lays out text, keeping track of SGR sequences and wrapping at the frame width if enabled
+    pub fn print(&mut self, s: &str) {
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\n' => self.rows.push(Row::default()),
+                '\x1b' => {
+                    if chars.next() != Some('[') {
+                        continue;
+                    }
+                    let params = chars.as_str();
+                    let Some(end) = params.find(|c| matches!(c, '\x40'..='\x7e')) else {
+                        return;
+                    };
+                    chars = params[end + 1..].chars();
+                    if params[end..].starts_with('m') {
+                        self.set_style(&params[..end]);
+                    }
+                }
+                c => self.push(if c.is_control() { ' ' } else { c }),
+            }
+        }
+    }
+
+    fn set_style(&mut self, params: &str) {
+        if self.plain || params.is_empty() || params == "0" {
+            self.style = 0;
+            return;
+        }
+
+        self.style = self.intern(format!("{}\x1b[{params}m", self.styles[self.style]));
+    }
+
+    fn intern(&mut self, style: String) -> usize {
+        match self.styles.iter().position(|s| *s == style) {
+            Some(i) => i,
+            None => {
+                self.styles.push(style);
+                self.styles.len() - 1
+            }
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        let row = self.rows.last_mut().unwrap();
+        row.cells.push(Cell {
+            c,
+            style: self.style,
+        });
+        // wrap eagerly, so that a full row leaves room for the cursor after it
+        if self.wrap && row.cells.len() >= self.width {
+            row.wrapped = true;
+            self.rows.push(Row::default());
+        }
+    }
+
+    /// Marks everything printed so far in the current row as fixed, so it doesn't scroll
+    /// horizontally along with the rest of the row
+    pub fn anchor(&mut self) {
+        let row = self.rows.last_mut().unwrap();
+        row.fixed = Some(row.cells.len());
+    }
+
+    /// Position right after the last printed character
+    pub fn end(&self) -> (usize, usize) {
+        let last = self.rows.len() - 1;
+        (last, self.rows[last].cells.len())
+    }
+
+    /// Keeps only `height` rows starting at `top`, replacing the first and last rows with
+    /// indicators if there's more above or below
+    fn crop(&mut self, top: usize, height: usize) {
+        let below = self.rows.len() - top - height;
+        self.rows.truncate(top + height);
+        self.rows.drain(..top);
+
+        if height < 3 {
+            return;
+        }
+        if top > 0 {
+            self.rows[0] = self.indicator(&format!("↑ {} more lines", top + 1));
+        }
+        if below > 0 {
+            self.rows[height - 1] = self.indicator(&format!("↓ {} more lines", below + 1));
+        }
+    }
+
+    fn indicator(&mut self, text: &str) -> Row {
+        let style = self.intern("\x1b[2m".to_owned());
+        let cells = text.chars().take(self.width - 1);
+        Row {
+            cells: cells.map(|c| Cell { c, style }).collect(),
+            ..Row::default()
+        }
+    }
+
+    /// Shows `left..` of the scrolling part of each row, adding `<` and `>` where truncated
+    fn pan(&mut self, left: usize) {
+        let style = self.intern("\x1b[2m".to_owned());
+        let marker = |c| Cell { c, style };
+
+        for row in &mut self.rows {
+            let (fixed, start) = match row.fixed {
+                Some(fixed) => (fixed, fixed + left),
+                None => (0, 0),
+            };
+            let size = self.width.saturating_sub(fixed + 1).max(1);
+            let (before, after) = (row.cells.len() > fixed, row.cells.len() > start + size);
+
+            row.cells.truncate(start + size);
+            row.cells.drain(fixed..start.min(row.cells.len()));
+            if after {
+                *row.cells.last_mut().unwrap() = marker('>');
+            }
+            if start > fixed && before {
+                match row.cells.get_mut(fixed) {
+                    Some(cell) => *cell = marker('<'),
+                    None => row.cells.push(marker('<')),
+                }
+            }
+        }
+    }
+
+    /// Whether row `i` can be reached by letting the terminal wrap the previous one
+    fn continues(&self, i: usize) -> bool {
+        i > 0 && self.rows[i - 1].wrapped && !self.rows[i].cells.is_empty()
+    }
+
+    fn same(&self, a: Cell, other: &Frame, b: Cell) -> bool {
+        a.c == b.c && self.styles[a.style] == other.styles[b.style]
+    }
+}
+
+/// Draws [`Frame`]s to the terminal, remembering what is on screen
+pub struct Renderer {
+    prev: Option<Frame>,
+    /// Whether to wrap frames in synchronized output sequences
+    sync: bool,
+    /// How many synchronized updates are open, see [`begin`](Renderer::begin)
+    depth: usize,
+    /// Whether to keep writes to a minimum, for screen readers
+    accessible: bool,
+    /// Terminal width, as of the last resize
+    width: usize,
+    /// First row shown when the frame doesn't fit on screen
+    top: usize,
+    /// First column shown when scrolling horizontally
+    left: usize,
+    /// Terminal cursor position, relative to the first row of the frame
+    row: usize,
+    col: usize,
+}
+
+impl Renderer {
+    pub const fn new(sync: bool) -> Self {
+        Self {
+            prev: None,
+            sync,
+            depth: 0,
+            accessible: false,
+            width: 0,
+            top: 0,
+            left: 0,
+            row: 0,
+            col: 0,
+        }
+    }
+
+    /// A renderer for screen readers, which moves the cursor relatively and inserts or deletes
+    /// characters in place instead of rewriting the rest of the line
+    pub fn accessible() -> Self {
+        Self {
+            accessible: true,
+            ..Self::new(false)
+        }
+    }
+
+    /// Starts a synchronized update, so that everything written until [`end`](Renderer::end) is
+    /// shown at once. Updates may be nested, in which case only the outermost one counts
+    pub fn begin(&mut self, w: &mut impl Write) -> io::Result<()> {
+        self.depth += 1;
+        if self.sync && self.depth == 1 {
+            write!(w, "\x1b[?2026h")?;
+        }
+        Ok(())
+    }
+
+    /// Ends a synchronized update started with [`begin`](Renderer::begin)
+    pub fn end(&mut self, w: &mut impl Write) -> io::Result<()> {
+        self.depth = self.depth.saturating_sub(1);
+        if self.sync && self.depth == 0 {
+            write!(w, "\x1b[?2026l")?;
+        }
+        Ok(())
+    }
+
+    /// Forgets what was drawn, starting the next frame at the cursor's current line
+    pub fn reset(&mut self) {
+        self.prev = None;
+        self.top = 0;
+        self.left = 0;
+        self.row = 0;
+        self.col = 0;
+    }
+
+    /// Erases everything drawn, leaving the cursor where the frame started
+    pub fn clear(&mut self, w: &mut impl Write) -> io::Result<()> {
+        self.move_to(w, (0, 0))?;
+        write!(w, "\x1b[J")?;
+        self.reset();
+        Ok(())
+    }
+
+    /// Moves to the line after everything drawn, leaving it on screen
+    pub fn move_past(&mut self, w: &mut impl Write) -> io::Result<()> {
+        if let Some(prev) = self.prev.take() {
+            self.move_to(w, prev.end())?;
+            write!(w, "\r\n")?;
+        }
+        self.reset();
+        Ok(())
+    }
+
+    /// Accounts for a terminal resize, forcing a full redraw on the next frame if the width changed
+    pub fn resize(&mut self, width: usize) {
+        self.width = width;
+        let Some(prev) = self.prev.take_if(|prev| prev.width != width) else {
+            return;
+        };
+
+        // most terminals rewrap soft-wrapped lines when resized, so the cursor likely moved
+        let mut row = 0;
+        let mut len = 0;
+        for (i, r) in prev.rows.iter().enumerate() {
+            if i == self.row {
+                row += (len + self.col.min(r.cells.len())) / width;
+                break;
+            }
+            len += r.cells.len();
+            if !r.wrapped {
+                row += len / width + 1;
+                len = 0;
+            }
+        }
+        self.row = row;
+    }
+
+    /// Draws a frame and moves the cursor to the given row and column
+    ///
+    /// Frames taller (or wider, if not wrapping) than the screen are scrolled to keep the cursor
+    /// in view.
+    pub fn render(
+        &mut self,
+        w: &mut impl Write,
+        mut frame: Frame,
+        (row, col): (usize, usize),
+    ) -> io::Result<()> {
+        let col = self.pan(&mut frame, row, col);
+        let cursor = (self.scroll(&mut frame, row), col);
+
+        self.begin(w)?;
+        let result = match self.prev.take() {
+            Some(prev) if prev.width == frame.width => self.diff(w, &prev, &frame),
+            _ => self.full(w, &frame),
+        };
+        let result = result.and_then(|()| self.move_to(w, cursor));
+        self.prev = Some(frame);
+        self.end(w)?;
+        result
+    }
+
+    /// Draws the whole frame, even if it doesn't fit on screen, and moves to the next line
+    pub fn finish(&mut self, w: &mut impl Write, mut frame: Frame) -> io::Result<()> {
+        self.begin(w)?;
+        let result = if frame.rows.len() <= frame.height {
+            let end = frame.end();
+            self.render(w, frame, end)
+        } else {
+            self.pan(&mut frame, 0, 0);
+            self.full(w, &frame)
+        };
+        let result = result.and_then(|()| write!(w, "\r\n"));
+        self.end(w)?;
+        self.reset();
+        result
+    }
+
+    /// Crops a frame taller than the screen, returning the row the cursor ends up on
+    fn scroll(&mut self, frame: &mut Frame, row: usize) -> usize {
+        let (len, height) = (frame.rows.len(), frame.height.max(1));
+        if len <= height {
+            self.top = 0;
+            return row;
+        }
+
+        self.top = window(row, len, height, self.top);
+        frame.crop(self.top, height);
+        row - self.top
+    }
+
+    /// Scrolls a non-wrapping frame sideways, returning the column the cursor ends up on
+    fn pan(&mut self, frame: &mut Frame, row: usize, col: usize) -> usize {
+        if frame.wrap {
+            return col;
+        }
+
+        let fixed = frame.rows[row].fixed.unwrap_or(0).min(col);
+        let size = frame.width.saturating_sub(fixed + 1).max(1);
+        // the cursor can also be right after the last character
+        let len = frame.rows[row].cells.len() - fixed + 1;
+        self.left = window(col - fixed, len, size, self.left);
+        frame.pan(self.left);
+        col - self.left
+    }
+
+    fn full(&mut self, w: &mut impl Write, frame: &Frame) -> io::Result<()> {
+        self.move_to(w, (0, 0))?;
+        write!(w, "\r\x1b[J")?;
+
+        let mut style = 0;
+        for (i, row) in frame.rows.iter().enumerate() {
+            // let the terminal wrap by itself, so it knows this is a single line
+            if i > 0 && !frame.continues(i) {
+                write!(w, "\r\n")?;
+            }
+            write_cells(w, frame, &row.cells, &mut style)?;
+            self.row = i;
+            self.col = row.cells.len();
+        }
+
+        reset_style(w, &mut style)
+    }
+
+    fn diff(&mut self, w: &mut impl Write, prev: &Frame, frame: &Frame) -> io::Result<()> {
+        let mut style = 0;
+        for (i, row) in frame.rows.iter().enumerate() {
+            let Some(old) = prev.rows.get(i) else {
+                if frame.continues(i) {
+                    // rewrite the last cell of the previous row, so the terminal wraps by itself
+                    let above = &frame.rows[i - 1].cells;
+                    self.move_to(w, (i - 1, above.len() - 1))?;
+                    write_cells(w, frame, &above[above.len() - 1..], &mut style)?;
+                } else {
+                    self.move_to(w, (i, 0))?;
+                }
+                write_cells(w, frame, &row.cells, &mut style)?;
+                self.row = i;
+                self.col = row.cells.len();
+                continue;
+            };
+
+            let wrapped = row.wrapped || old.wrapped;
+            let (new, old) = (&row.cells, &old.cells);
+            let common = new.len().min(old.len());
+            let differs = |&j: &usize| !frame.same(new[j], prev, old[j]);
+            let start = (0..common).find(differs).unwrap_or(common);
+            if start == new.len() && start == old.len() {
+                continue;
+            }
+
+            if self.accessible && !wrapped {
+                let tail = (new[start..].iter().rev())
+                    .zip(old[start..].iter().rev())
+                    .take_while(|(&a, &b)| frame.same(a, prev, b))
+                    .count();
+                if tail > 0 {
+                    // shift the rest of the row over, rather than writing it again
+                    let (added, removed) = (new.len() - start - tail, old.len() - start - tail);
+                    self.move_to(w, (i, start))?;
+                    if removed > 0 {
+                        write!(w, "\x1b[{removed}P")?;
+                    }
+                    if added > 0 {
+                        write!(w, "\x1b[{added}@")?;
+                        write_cells(w, frame, &new[start..start + added], &mut style)?;
+                    }
+                    self.col = start + added;
+                    continue;
+                }
+            }
+            let end = match new.len() == old.len() {
+                true => (start..common).rev().find(differs).map_or(start, |j| j + 1),
+                false => new.len(),
+            };
+
+            self.move_to(w, (i, start))?;
+            write_cells(w, frame, &new[start..end], &mut style)?;
+            self.col = end;
+            if old.len() > new.len() {
+                reset_style(w, &mut style)?;
+                write!(w, "\x1b[K")?;
+            }
+        }
+
+        if prev.rows.len() > frame.rows.len() {
+            reset_style(w, &mut style)?;
+            self.move_to(w, frame.end())?;
+            write!(w, "\x1b[J")?;
+        }
+
+        reset_style(w, &mut style)
+    }
+
+    fn move_to(&mut self, w: &mut impl Write, (row, col): (usize, usize)) -> io::Result<()> {
+        if (row, col) == (self.row, self.col) {
+            return Ok(());
+        }
+        // past the last column, the terminal is still on it, waiting to wrap
+        if self.accessible && row == self.row && self.col < self.width {
+            if col < self.col {
+                write!(w, "\x1b[{}D", self.col - col)?;
+            } else {
+                write!(w, "\x1b[{}C", col - self.col)?;
+            }
+            self.col = col;
+            return Ok(());
+        }
+
+        write!(w, "\r")?;
+        if row > self.row {
+            // unlike moving the cursor down, newlines scroll if needed
+            write!(w, "{}", "\n".repeat(row - self.row))?;
+        } else if row < self.row {
+            write!(w, "\x1b[{}A", self.row - row)?;
+        }
+        if col > 0 {
+            write!(w, "\x1b[{col}C")?;
+        }
+
+        self.row = row;
+        self.col = col;
+        Ok(())
+    }
+}
+
+/// Moves a window of `size` over `len` items so that it contains `pos`, keeping it one item
+/// away from the edges if there's more past them, so there's room for indicators
+pub(crate) fn window(pos: usize, len: usize, size: usize, start: usize) -> usize {
+    if len <= size {
+        return 0;
+    }
+
+    let margin = usize::from(size >= 3);
+    let mut start = start.min(len - size);
+    if pos < start + margin {
+        start = pos.saturating_sub(margin);
+    } else if pos + margin >= start + size {
+        start = (pos + margin + 1 - size).min(len - size);
+    }
+
+    start
+}
+
+fn write_cells(
+    w: &mut impl Write,
+    frame: &Frame,
+    cells: &[Cell],
+    style: &mut usize,
+) -> io::Result<()> {
+    for cell in cells {
+        if frame.styles[cell.style] != frame.styles[*style] {
+            write!(w, "\x1b[m{}", frame.styles[cell.style])?;
+            *style = cell.style;
+        }
+        write!(w, "{}", cell.c)?;
+    }
+
+    Ok(())
+}
+
+fn reset_style(w: &mut impl Write, style: &mut usize) -> io::Result<()> {
+    if *style != 0 {
+        write!(w, "\x1b[m")?;
+        *style = 0;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(text: &str) -> Frame {
+        let mut frame = Frame::new(10, 5, true);
+        frame.plain = false;
+        frame.print(text);
+        frame
+    }
+
+    fn rows(frame: &Frame) -> Vec<String> {
+        let text = |row: &Row| row.cells.iter().map(|cell| cell.c).collect();
+        frame.rows.iter().map(text).collect()
+    }
+
+    fn render(screen: &mut Renderer, text: &str) -> String {
+        let mut out = Vec::new();
+        let frame = frame(text);
+        let end = frame.end();
+        screen.render(&mut out, frame, end).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn first_frame() {
+        let mut screen = Renderer::new(false);
+        assert_eq!(render(&mut screen, "> abc"), "\r\x1b[J> abc");
+    }
+
+    #[test]
+    fn changed_cells() {
+        let mut screen = Renderer::new(false);
+        render(&mut screen, "> abc");
+        assert_eq!(render(&mut screen, "> abc"), "");
+        assert_eq!(render(&mut screen, "> abd"), "\r\x1b[4Cd");
+        assert_eq!(render(&mut screen, "> xbd"), "\r\x1b[2Cx\r\x1b[5C");
+        assert_eq!(render(&mut screen, "> xbdef"), "ef");
+    }
+
+    #[test]
+    fn shorter_frame() {
+        let mut screen = Renderer::new(false);
+        render(&mut screen, "> abc\n. def");
+        assert_eq!(render(&mut screen, "> abc\n. d"), "\r\x1b[3C\x1b[K");
+        assert_eq!(render(&mut screen, "> a"), "\r\x1b[1A\x1b[3C\x1b[K\x1b[J");
+    }
+
+    #[test]
+    fn styles() {
+        let mut screen = Renderer::new(false);
+        assert_eq!(
+            render(&mut screen, "> \x1b[1mab\x1b[m"),
+            "\r\x1b[J> \x1b[m\x1b[1mab\x1b[m"
+        );
+        // only the style changed
+        assert_eq!(
+            render(&mut screen, "> \x1b[1ma\x1b[32mb"),
+            "\r\x1b[3C\x1b[m\x1b[1m\x1b[32mb\x1b[m"
+        );
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut screen = Renderer::new(false);
+        // the terminal wraps by itself
+        assert_eq!(render(&mut screen, "> abcdefghij"), "\r\x1b[J> abcdefghij");
+        // a full row still leaves an empty one after it for the cursor
+        assert_eq!(render(&mut screen, "> abcdefgh"), "\r\x1b[K");
+        assert_eq!(
+            render(&mut screen, "> abcdefg"),
+            "\r\x1b[1A\x1b[9C\x1b[K\x1b[J"
+        );
+    }
+
+    #[test]
+    fn window_fits() {
+        assert_eq!(window(3, 5, 5, 0), 0);
+        assert_eq!(window(3, 5, 10, 2), 0);
+    }
+
+    #[test]
+    fn window_margin() {
+        // kept one item away from the edges, so there's room for indicators
+        assert_eq!(window(0, 10, 5, 0), 0);
+        assert_eq!(window(3, 10, 5, 0), 0);
+        assert_eq!(window(4, 10, 5, 0), 1);
+        assert_eq!(window(3, 10, 5, 2), 2);
+        assert_eq!(window(2, 10, 5, 2), 1);
+        assert_eq!(window(9, 10, 5, 0), 5);
+        assert_eq!(window(9, 10, 5, 8), 5);
+        // too small for indicators
+        assert_eq!(window(2, 10, 2, 0), 1);
+        assert_eq!(window(1, 10, 2, 1), 1);
+    }
+
+    #[test]
+    fn crop() {
+        let mut tall = frame("0\n1\n2\n3\n4\n5");
+        tall.crop(1, 4);
+        // indicators are cut to fit, leaving room for the cursor
+        assert_eq!(rows(&tall), ["↑ 2 more ", "2", "3", "↓ 2 more "]);
+
+        let mut short = frame("0\n1\n2\n3");
+        short.crop(2, 2);
+        assert_eq!(rows(&short), ["2", "3"]);
+    }
+
+    fn scrolling(text: &str) -> Frame {
+        let mut frame = Frame::new(10, 5, false);
+        frame.print("> ");
+        frame.anchor();
+        frame.print(text);
+        frame
+    }
+
+    #[test]
+    fn pan() {
+        let mut start = scrolling("abcdefghijklmno");
+        start.pan(0);
+        assert_eq!(rows(&start), ["> abcdef>"]);
+
+        let mut middle = scrolling("abcdefghijklmno");
+        middle.pan(3);
+        assert_eq!(rows(&middle), ["> <efghi>"]);
+
+        let mut end = scrolling("abcdefghijklmno");
+        end.pan(10);
+        assert_eq!(rows(&end), ["> <lmno"]);
+    }
+
+    #[test]
+    fn pan_to_cursor() {
+        let mut screen = Renderer::new(false);
+        let mut frame = scrolling("abcdefghijklmno");
+        assert_eq!(screen.pan(&mut frame, 0, 17), 8);
+        assert_eq!(rows(&frame), ["> <klmno"]);
+
+        // moving back only scrolls once the cursor reaches the edge
+        let mut frame = scrolling("abcdefghijklmno");
+        assert_eq!(screen.pan(&mut frame, 0, 13), 4);
+        assert_eq!(rows(&frame), ["> <klmno"]);
+    }
+}
@@ -0,0 +1,824 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::{self, Write};
+
+use crate::ansi::Reader;
+use crate::render::{Frame, Renderer};
+use crate::{Completion, Echo, Editor, Event, Prompt, Validation};
+
+/// Outcome of feeding input to a [`PromptState`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Status {
+    /// The read is still in progress
+    Pending,
+    /// The input was submitted with [`Event::Enter`]
+    Submitted(String),
+    /// [`Event::Interrupt`] was sent on an empty buffer
+    Interrupted,
+    /// [`Event::Eof`] was sent on an empty buffer
+    Eof,
+}
+
+struct CompletionState {
+    range: std::ops::Range<usize>,
+    results: Vec<String>,
+    current: usize,
+    buffer: String,
+}
+
+/// The editing state of a single read, for driving a [`Prompt`] without blocking
+///
+/// Created with [`Prompt::start`]. Instead of reading input by itself, it's given input through
+/// [`feed`](PromptState::feed) whenever it's available, which makes it possible to embed a prompt
+/// in an event loop. [`Prompt::read_from`] is itself built on top of this.
+///
+/// The terminal should be in raw mode while feeding input, see [`RawMode`](crate::RawMode), and
+/// output is expected to be a terminal. Writes to the output aren't flushed.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use pomprt::Status;
+/// use std::io::{self, Read, Write};
+///
+/// let mut prompt = pomprt::new(">> ");
+/// let _raw = pomprt::RawMode::acquire();
+/// let mut stdout = io::stdout();
+/// let mut state = prompt.start(&mut stdout)?;
+/// stdout.flush()?;
+///
+/// let mut buf = [0; 64];
+/// loop {
+///     // ...wait for stdin to be readable...
+///     let n = io::stdin().read(&mut buf)?;
+///     let status = state.feed(&buf[..n], &mut stdout)?;
+///     stdout.flush()?;
+///     match status {
+///         Status::Pending => continue,
+///         Status::Submitted(input) => break println!("{input}"),
+///         Status::Interrupted | Status::Eof => break,
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct PromptState<'p, 'a, E: Editor> {
+    prompt: &'p mut Prompt<'a, E>,
+    buffer: String,
+    cursor: usize,
+    history_entry: usize,
+    saved_entry: String,
+    completion: Option<CompletionState>,
+    /// Message from [`Editor::validate`], shown until the next event
+    invalid: Option<String>,
+    screen: Renderer,
+    /// Input not yet turned into events, usually an incomplete sequence
+    pending: Vec<u8>,
+}
+
+impl<'p, 'a, E: Editor> PromptState<'p, 'a, E> {
+    pub(crate) fn new(prompt: &'p mut Prompt<'a, E>, sync: bool) -> Self {
+        let screen = match prompt.accessible {
+            true => Renderer::accessible(),
+            false => Renderer::new(sync),
+        };
+        Self {
+            history_entry: prompt.history.len(),
+            prompt,
+            buffer: String::with_capacity(128),
+            cursor: 0,
+            saved_entry: String::new(),
+            completion: None,
+            invalid: None,
+            screen,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The current input
+    pub fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    /// The cursor position in [`buffer`](PromptState::buffer), in bytes
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replaces the current input, placing the cursor at byte offset `cursor`, or at the end if
+    /// it's past it. The change is only shown on the next [`redraw`](PromptState::redraw)
+    pub fn set_buffer(&mut self, text: &str, cursor: usize) {
+        self.buffer.clear();
+        self.buffer.push_str(text);
+        self.cursor = cursor.min(text.len());
+        while !text.is_char_boundary(self.cursor) {
+            self.cursor -= 1;
+        }
+        self.completion = None;
+    }
+
+    /// Feeds raw input, turning it into events with [`Editor::next_event`] and handling them
+    ///
+    /// Incomplete sequences are kept until the rest of them is fed. Once the read is finished,
+    /// any input after the last event is left unhandled.
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing to `w` or [`Editor::next_event`] fails.
+    pub fn feed(&mut self, input: &[u8], w: &mut impl Write) -> io::Result<Status> {
+        self.synced(w, |state, w| state.feed_synced(input, w))
+    }
+
+    fn feed_synced(&mut self, input: &[u8], w: &mut impl Write) -> io::Result<Status> {
+        let secret = self.prompt.echo.is_secret();
+        if secret {
+            reserve_bytes(&mut self.pending, input.len());
+        }
+        self.pending.extend_from_slice(input);
+
+        let mut consumed = 0;
+        let mut status = Status::Pending;
+        while consumed < self.pending.len() {
+            let mut rest = &self.pending[consumed..];
+            let mut reader = Reader::new(&mut rest);
+            let event = self.prompt.editor.next_event(&mut reader);
+            if secret {
+                reader.wipe();
+            }
+            drop(reader);
+            let event = match event {
+                Ok(event) => event,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            consumed = self.pending.len() - rest.len();
+
+            status = self.apply(event, w)?;
+            if status != Status::Pending {
+                break;
+            }
+        }
+        self.pending.drain(..consumed);
+        if secret {
+            // what's left was moved over, so the consumed bytes may still be past the end
+            zero_spare(&mut self.pending);
+        }
+
+        if status == Status::Pending && consumed > 0 {
+            self.redraw(w)?;
+        }
+        Ok(status)
+    }
+
+    /// Handles a single event, as if it came from [`Editor::next_event`]
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing to `w` fails.
+    pub fn feed_event(&mut self, event: Event, w: &mut impl Write) -> io::Result<Status> {
+        self.synced(w, |state, w| {
+            let status = state.apply(event, w)?;
+            if status == Status::Pending {
+                state.redraw(w)?;
+            }
+            Ok(status)
+        })
+    }
+
+    /// Draws the prompt and current input, picking up changes in terminal size
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing to `w` fails.
+    pub fn redraw(&mut self, w: &mut impl Write) -> io::Result<()> {
+        let size @ (mut width, _) = term_size();
+        self.screen.resize(width);
+        if !self.wraps() {
+            width = usize::MAX;
+        }
+
+        let before = self.prompt.echo.mask(&self.buffer[..self.cursor]);
+        let mut col = 0;
+        let line = count_lines(
+            self.prompt
+                .buf_lengths(&before)
+                .inspect(|len| col = len % width),
+            width,
+        );
+
+        let frame = self.frame(size, false);
+        self.screen.render(w, frame, (line, col))
+    }
+
+    /// Prints a message above the prompt, redrawing it afterwards. A newline should be included
+    ///
+    /// In [accessible](Prompt::set_accessible) mode, it's printed below the input instead.
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing to `w` fails.
+    pub fn print_above(&mut self, msg: &str, w: &mut impl Write) -> io::Result<()> {
+        self.synced(w, |state, w| {
+            if state.prompt.accessible {
+                state.screen.move_past(w)?;
+            } else {
+                state.screen.clear(w)?;
+            }
+            write!(w, "{msg}")?;
+            state.redraw(w)
+        })
+    }
+
+    /// Ends the read early, moving past the prompt and returning the input so far. Secret input
+    /// is zeroed and not returned.
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing to `w` fails.
+    pub fn abort(&mut self, w: &mut impl Write) -> io::Result<String> {
+        self.finish(w)?;
+        let buffer = std::mem::take(&mut self.buffer);
+        if self.prompt.echo.is_secret() {
+            zero_string(buffer);
+            return Ok(String::new());
+        }
+        Ok(buffer)
+    }
+
+    /// Puts the terminal back in raw mode after the process was stopped and continued, and
+    /// redraws the prompt from scratch
+    pub(crate) fn resume(&mut self, w: &mut impl Write) -> io::Result<()> {
+        rawrrr::enable_raw();
+        self.screen.reset();
+        self.redraw(w)
+    }
+
+    /// Input fed after the read finished
+    pub(crate) fn leftover(&self) -> &[u8] {
+        &self.pending
+    }
+
+    fn apply(&mut self, event: Event, w: &mut impl Write) -> io::Result<Status> {
+        let Self {
+            prompt,
+            buffer,
+            cursor,
+            ..
+        } = self;
+        let secret = prompt.echo.is_secret();
+        let cur_completion = self.completion.take();
+        // lines printed below the input in accessible mode, rather than drawn as hints
+        let mut announce = Vec::new();
+        self.invalid = None;
+        // keep the previous input around in case the new one is rejected
+        let limited = prompt.max_len.is_some() || prompt.filter.is_some();
+        let before = (limited && matches!(event, Event::Insert(_) | Event::Tab))
+            .then(|| (buffer.clone(), *cursor));
+        match event {
+            Event::Insert(c) => {
+                if secret {
+                    reserve_secret(buffer);
+                }
+                prompt.editor.insert(buffer, cursor, c);
+            }
+            Event::Enter if !secret && prompt.editor.is_multiline(buffer, *cursor) => {
+                prompt.editor.insert(buffer, cursor, '\n');
+            }
+            Event::Enter => match prompt.editor.validate(buffer) {
+                Validation::Valid => {
+                    if !secret && !prompt.history.last().is_some_and(|e| e.eq(buffer)) {
+                        prompt.history.push(buffer.clone());
+                    }
+                    self.finish(w)?;
+                    return Ok(Status::Submitted(std::mem::take(&mut self.buffer)));
+                }
+                Validation::Incomplete if !secret => prompt.editor.insert(buffer, cursor, '\n'),
+                Validation::Incomplete => {}
+                Validation::Invalid(msg) if prompt.accessible => announce.push(msg),
+                Validation::Invalid(msg) => self.invalid = Some(msg),
+            },
+            Event::Backspace if *cursor > 0 => loop {
+                *cursor -= 1;
+                if buffer.is_char_boundary(*cursor) {
+                    buffer.remove(*cursor);
+                    break;
+                }
+            },
+            Event::Tab | Event::Up | Event::Down if secret => {}
+            Event::Tab => {
+                let fresh = cur_completion.is_none();
+                self.completion = cur_completion.or_else(|| {
+                    prompt
+                        .editor
+                        .complete(buffer, *cursor)
+                        .map(|Completion(range, results)| CompletionState {
+                            range,
+                            results,
+                            current: 0,
+                            buffer: buffer.clone(),
+                        })
+                });
+
+                match self.completion.as_mut() {
+                    Some(c) if c.results.is_empty() => {}
+                    // automatically submit if only one entry is present
+                    Some(c) if c.results.len() == 1 => {
+                        buffer.replace_range(c.range.clone(), &c.results[0]);
+                        *cursor = c.range.start + c.results[0].len();
+                        self.completion = None;
+                    }
+                    Some(c) => {
+                        if fresh && prompt.accessible {
+                            announce.clone_from(&c.results);
+                        }
+                        buffer.clone_from(&c.buffer);
+                        buffer.replace_range(c.range.clone(), &c.results[c.current]);
+                        *cursor = c.range.start + c.results[c.current].len();
+                        c.current = (c.current + 1) % c.results.len();
+                    }
+                    None => prompt.editor.indent(buffer, cursor),
+                }
+            }
+            Event::Left if *cursor > 0 => loop {
+                *cursor -= 1;
+                if buffer.is_char_boundary(*cursor) {
+                    break;
+                }
+            },
+            Event::Right if *cursor < buffer.len() => loop {
+                *cursor += 1;
+                if buffer.is_char_boundary(*cursor) {
+                    break;
+                }
+            },
+            Event::Home => *cursor = 0,
+            Event::End => *cursor = buffer.len(),
+            Event::Interrupt if buffer.is_empty() => {
+                self.finish(w)?;
+                return Ok(Status::Interrupted);
+            }
+            Event::Eof if buffer.is_empty() => {
+                self.finish(w)?;
+                return Ok(Status::Eof);
+            }
+            Event::Interrupt => {
+                self.finish(w)?;
+                self.cursor = 0;
+                self.buffer.clear();
+            }
+            #[cfg(all(unix, feature = "suspend"))]
+            Event::Suspend => {
+                // don't leave the terminal holding back output while stopped
+                self.screen.end(w)?;
+                w.flush()?;
+                crate::sys::suspend();
+                self.screen.reset();
+                self.screen.begin(w)?;
+            }
+            #[cfg(feature = "abort")]
+            Event::Abort => {
+                rawrrr::disable_raw();
+                std::process::abort()
+            }
+            Event::Up if self.history_entry > 0 => {
+                if self.history_entry == prompt.history.len() {
+                    self.saved_entry = std::mem::take(buffer);
+                }
+                self.history_entry -= 1;
+                buffer.clone_from(
+                    prompt
+                        .history
+                        .get(self.history_entry)
+                        .unwrap_or(&self.saved_entry),
+                );
+                *cursor = buffer.len();
+            }
+            Event::Down if self.history_entry < prompt.history.len() => {
+                self.history_entry += 1;
+                buffer.clone_from(
+                    prompt
+                        .history
+                        .get(self.history_entry)
+                        .unwrap_or(&self.saved_entry),
+                );
+                *cursor = buffer.len();
+            }
+            Event::Clear => {
+                write!(w, "\x1b[H\x1b[2J")?;
+                self.screen.reset();
+            }
+            Event::LeftWord => {
+                while *cursor > 0 {
+                    *cursor -= 1;
+                    if !buffer[..*cursor].ends_with(E::is_keyword) {
+                        break;
+                    }
+                }
+            }
+            Event::RightWord => {
+                while *cursor < buffer.len() {
+                    *cursor += 1;
+                    if !buffer[*cursor..].starts_with(E::is_keyword) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if !announce.is_empty() {
+            self.screen.move_past(w)?;
+            for line in announce {
+                write!(w, "{line}\r\n")?;
+            }
+        }
+
+        if let Some((mut before, cursor)) = before {
+            if !self.prompt.accepts(&before, &self.buffer) {
+                std::mem::swap(&mut self.buffer, &mut before);
+                self.cursor = cursor;
+                self.completion = None;
+                write!(w, "\x07")?;
+            }
+            if secret {
+                zero_string(before);
+            }
+        }
+
+        Ok(Status::Pending)
+    }
+
+    /// Lays out the prompt and the current input. Unless `done`, a hint is shown below it
+    fn frame(&self, (width, height): (usize, usize), done: bool) -> Frame {
+        let mut frame = Frame::new(width, height, self.wraps() || done);
+        let editor = &self.prompt.editor;
+        let accessible = self.prompt.accessible;
+
+        let hl = match (&self.prompt.placeholder, self.prompt.echo) {
+            (text, _) if self.buffer.is_empty() && !text.is_empty() && !done && !accessible => {
+                editor.highlight_placeholder(text)
+            }
+            (_, Echo::Normal) if accessible => self.buffer.clone(),
+            (_, Echo::Normal) => editor.highlight(&self.buffer),
+            (_, echo) => echo.mask(&self.buffer).into_owned(),
+        };
+        let prompt = editor.highlight_prompt(&self.prompt.prompt, false);
+        let multiline = editor.highlight_prompt(&self.prompt.multiline, true);
+        let mut cur_prompt = &prompt;
+        for (i, line) in hl.split('\n').enumerate() {
+            if i > 0 {
+                frame.print("\n");
+            }
+            frame.print(cur_prompt);
+            frame.print("\x1b[m");
+            frame.anchor();
+            frame.print(line);
+            frame.print("\x1b[m");
+            cur_prompt = &multiline;
+        }
+
+        let hint = match &self.invalid {
+            Some(msg) => Some(msg.clone()),
+            None if self.prompt.echo.is_secret() => None,
+            None => editor.hint(&self.buffer),
+        };
+        if let Some(hint) = hint.filter(|_| !done && !accessible) {
+            frame.print("\n");
+            frame.print(&editor.highlight_hint(&hint));
+        }
+
+        frame
+    }
+
+    fn wraps(&self) -> bool {
+        self.prompt.wrap || self.prompt.accessible
+    }
+
+    /// Runs `f` as a single synchronized update, so that nothing is shown half drawn
+    fn synced<W: Write, T>(
+        &mut self,
+        w: &mut W,
+        f: impl FnOnce(&mut Self, &mut W) -> io::Result<T>,
+    ) -> io::Result<T> {
+        self.screen.begin(w)?;
+        let result = f(self, w);
+        self.screen.end(w)?;
+        result
+    }
+
+    /// Draws the input without any hints and moves to a new line
+    fn finish(&mut self, w: &mut impl Write) -> io::Result<()> {
+        let frame = self.frame(term_size(), true);
+        self.screen.finish(w, frame)
+    }
+}
+
+impl<E: Editor> Drop for PromptState<'_, '_, E> {
+    fn drop(&mut self) {
+        if self.prompt.echo.is_secret() {
+            zero_string(std::mem::take(&mut self.buffer));
+            self.pending.clear();
+            zero_spare(&mut self.pending);
+        }
+    }
+}
+
+impl<'a, E: Editor> Prompt<'a, E> {
+    /// Start a read driven by feeding input, drawing the prompt to `w`
+    ///
+    /// See [`PromptState`]
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing to `w` fails.
+    pub fn start(&mut self, w: &mut impl Write) -> io::Result<PromptState<'_, 'a, E>> {
+        let mut state = PromptState::new(self, false);
+        state.redraw(w)?;
+        Ok(state)
+    }
+
+    /// Returns `false` if an edit from `old` to `new` breaks the length limit or filter
+    fn accepts(&self, old: &str, new: &str) -> bool {
+        let filtered = |s: &str| match &self.filter {
+            Some(f) => s.chars().filter(|&c| !f(c)).count(),
+            None => 0,
+        };
+        let len = |s: &str| graphemes(s).saturating_sub(self.max_len.unwrap_or(usize::MAX));
+        // it's fine for input to be over the limit already, as long as it isn't made worse
+        filtered(new) <= filtered(old) && len(new) <= len(old)
+    }
+
+    fn buf_lengths<'b>(&self, buf: &'b str) -> impl Iterator<Item = usize> + 'b {
+        let prompt = self.prompt.chars().count();
+        let multiline = self.multiline.chars().count();
+        let mut cur_prompt = prompt;
+        buf.split('\n').map(move |line| {
+            let len = cur_prompt + line.chars().count();
+            cur_prompt = multiline;
+            len
+        })
+    }
+}
+
+pub(crate) fn term_size() -> (usize, usize) {
+    match rawrrr::get_size() {
+        Some((w, h)) if w > 0 && h > 0 => (w, h),
+        _ => (80, 24),
+    }
+}
+
+fn count_lines(lengths: impl Iterator<Item = usize>, width: usize) -> usize {
+    lengths.map(|x| x / width + 1).sum::<usize>() - 1
+}
+
+/// Counts grapheme clusters, roughly
+///
+/// Combining marks, variation selectors, emoji modifiers and tags are counted as part of the
+/// previous char, as is anything joined to it with a zero-width joiner. Regional indicators are
+/// paired up into flags.
+fn graphemes(s: &str) -> usize {
+    let mut joined = false;
+    // a regional indicator still waiting for the other half of its flag
+    let mut flag = false;
+    s.chars()
+        .filter(|&c| {
+            let regional = matches!(c, '\u{1f1e6}'..='\u{1f1ff}');
+            let extends = joined
+                || (regional && flag)
+                || matches!(c,
+                    '\u{300}'..='\u{36f}'
+                    | '\u{1ab0}'..='\u{1aff}'
+                    | '\u{1dc0}'..='\u{1dff}'
+                    | '\u{200d}'
+                    | '\u{20d0}'..='\u{20ff}'
+                    | '\u{fe00}'..='\u{fe0f}'
+                    | '\u{fe20}'..='\u{fe2f}'
+                    | '\u{1f3fb}'..='\u{1f3ff}'
+                    | '\u{e0020}'..='\u{e007f}'
+                    | '\u{e0100}'..='\u{e01ef}');
+            joined = c == '\u{200d}';
+            flag = regional && !flag;
+            !extends
+        })
+        .count()
+}
+
+/// Makes room for at least one more char in secret input, without leaving a copy of it behind
+pub(crate) fn reserve_secret(buffer: &mut String) {
+    if buffer.capacity() - buffer.len() < 4 {
+        let mut grown = String::with_capacity(buffer.capacity() * 2 + 4);
+        grown.push_str(buffer);
+        zero_string(std::mem::replace(buffer, grown));
+    }
+}
+
+/// Makes room for `additional` more secret bytes, like [`reserve_secret`]
+fn reserve_bytes(bytes: &mut Vec<u8>, additional: usize) {
+    if bytes.capacity() - bytes.len() < additional {
+        let mut grown = Vec::with_capacity((bytes.len() + additional).max(bytes.capacity() * 2));
+        grown.extend_from_slice(bytes);
+        let mut old = std::mem::replace(bytes, grown);
+        old.clear();
+        zero_spare(&mut old);
+    }
+}
+
+pub(crate) fn zero_string(s: String) {
+    let mut bytes = s.into_bytes();
+    bytes.clear();
+    zero_spare(&mut bytes);
+}
+
+/// Zeroes the spare capacity of `bytes`, where anything removed may still be
+pub(crate) fn zero_spare(bytes: &mut Vec<u8>) {
+    let len = bytes.len();
+    bytes.resize(bytes.capacity(), 0);
+    zero(&mut bytes[len..]);
+    bytes.truncate(len);
+}
+
+/// Overwrites `bytes` with zeroes, in a way that won't be optimized out
+pub(crate) fn zero(bytes: &mut [u8]) {
+    for b in bytes {
+        unsafe { std::ptr::write_volatile(b, 0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed<E: Editor>(state: &mut PromptState<'_, '_, E>, input: &[u8]) -> Status {
+        state.feed(input, &mut Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn split_char() {
+        let mut prompt = Prompt::new("> ");
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+        let bytes = "aé".as_bytes();
+
+        assert_eq!(feed(&mut state, &bytes[..2]), Status::Pending);
+        assert_eq!(state.buffer(), "a");
+        assert_eq!(feed(&mut state, &bytes[2..]), Status::Pending);
+        assert_eq!(state.buffer(), "aé");
+        assert_eq!(state.cursor(), 3);
+    }
+
+    #[test]
+    fn split_sequence() {
+        let mut prompt = Prompt::new("> ");
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+
+        feed(&mut state, b"ab\x1b");
+        assert_eq!((state.buffer(), state.cursor()), ("ab", 2));
+        feed(&mut state, b"[");
+        feed(&mut state, b"Dc");
+        assert_eq!((state.buffer(), state.cursor()), ("acb", 2));
+    }
+
+    #[test]
+    fn submit() {
+        let mut prompt = Prompt::new("> ");
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+
+        assert_eq!(
+            feed(&mut state, b"hi\rleft"),
+            Status::Submitted("hi".into())
+        );
+        assert_eq!(state.leftover(), b"left");
+        drop(state);
+        assert_eq!(prompt.history, ["hi"]);
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut prompt = Prompt::new("> ");
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+
+        // the first one only clears the input
+        assert_eq!(feed(&mut state, b"abc\x03"), Status::Pending);
+        assert_eq!(state.buffer(), "");
+        assert_eq!(feed(&mut state, b"\x03"), Status::Interrupted);
+    }
+
+    #[test]
+    fn eof() {
+        let mut prompt = Prompt::new("> ");
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+
+        assert_eq!(feed(&mut state, b"a\x04"), Status::Pending);
+        assert_eq!(feed(&mut state, b"\x7f\x04"), Status::Eof);
+    }
+
+    #[test]
+    fn history() {
+        let mut prompt = Prompt::new("> ");
+        prompt.history = vec!["one".into(), "two".into()];
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+
+        feed(&mut state, b"new\x1b[A");
+        assert_eq!((state.buffer(), state.cursor()), ("two", 3));
+        feed(&mut state, b"\x1b[A\x1b[A");
+        assert_eq!(state.buffer(), "one");
+        feed(&mut state, b"\x1b[B");
+        assert_eq!(state.buffer(), "two");
+        // back to what was being typed
+        feed(&mut state, b"\x1b[B");
+        assert_eq!(state.buffer(), "new");
+        assert_eq!(
+            feed(&mut state, b"\x1b[A\r"),
+            Status::Submitted("two".into())
+        );
+        drop(state);
+        // repeated entries are only kept once
+        assert_eq!(prompt.history, ["one", "two"]);
+    }
+
+    struct Port;
+
+    impl Editor for Port {
+        fn validate(&self, buffer: &str) -> Validation {
+            match buffer.parse::<u16>() {
+                Ok(_) => Validation::Valid,
+                Err(_) if buffer.ends_with(':') => Validation::Incomplete,
+                Err(_) => Validation::Invalid("not a port".into()),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let mut prompt = Prompt::with(Port, "port: ");
+        let mut out = Vec::new();
+        let mut state = prompt.start(&mut out).unwrap();
+
+        assert_eq!(state.feed(b"80a\r", &mut out).unwrap(), Status::Pending);
+        assert_eq!(state.invalid.as_deref(), Some("not a port"));
+        assert!(String::from_utf8_lossy(&out).contains("not a port"));
+        // shown until the next event
+        feed(&mut state, b"\x7f");
+        assert_eq!(state.invalid, None);
+        assert_eq!(feed(&mut state, b"\r"), Status::Submitted("80".into()));
+    }
+
+    #[test]
+    fn incomplete() {
+        let mut prompt = Prompt::with(Port, "port: ");
+        let mut state = prompt.start(&mut Vec::new()).unwrap();
+
+        assert_eq!(feed(&mut state, b"host:\r"), Status::Pending);
+        assert_eq!(state.buffer(), "host:\n");
+        assert_eq!(state.invalid, None);
+    }
+
+    #[test]
+    fn grapheme_count() {
+        assert_eq!(graphemes(""), 0);
+        assert_eq!(graphemes("abc"), 3);
+        assert_eq!(graphemes("e\u{301}"), 1);
+        assert_eq!(graphemes("👍🏽!"), 2);
+        assert_eq!(graphemes("👨\u{200d}👩\u{200d}👧"), 1);
+        assert_eq!(graphemes("🏴\u{e0067}\u{e0062}\u{e007f}"), 1);
+        assert_eq!(graphemes("🇺🇸"), 1);
+        assert_eq!(graphemes("🇺🇸🇫🇷🇩"), 3);
+    }
+
+    #[test]
+    fn max_len() {
+        let mut prompt = Prompt::new("> ");
+        prompt.set_max_len(Some(3));
+
+        assert!(prompt.accepts("ab", "abc"));
+        assert!(prompt.accepts("ab", "abe\u{301}"));
+        assert!(prompt.accepts("ab", "ab🇺🇸"));
+        assert!(!prompt.accepts("abc", "abcd"));
+        // input that's already too long can still be edited, as long as it doesn't grow
+        assert!(prompt.accepts("abcde", "abcd"));
+        assert!(prompt.accepts("abcde", "abxde"));
+        assert!(!prompt.accepts("abcde", "abcdef"));
+
+        let mut out = Vec::new();
+        let mut state = prompt.start(&mut out).unwrap();
+        state.feed(b"abcd", &mut out).unwrap();
+        assert_eq!(state.buffer(), "abc");
+        assert!(out.contains(&b'\x07'));
+    }
+
+    #[test]
+    fn filter() {
+        let allowed = String::from("0123456789");
+        let mut prompt = Prompt::new("> ");
+        prompt.set_filter(move |c| allowed.contains(c));
+
+        assert!(prompt.accepts("1", "12"));
+        assert!(!prompt.accepts("1", "1a"));
+        assert!(prompt.accepts("1a", "1"));
+        assert!(prompt.accepts("1a", "12a"));
+        assert!(!prompt.accepts("1a", "1ab"));
+    }
+}
@@ -0,0 +1,353 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Platform-specific bits: waiting for input and reacting to signals while a prompt is active
+
+use std::io::{self, Read};
+use std::sync::Mutex;
+
+#[cfg(unix)]
+pub use unix::*;
+
+#[cfg(not(unix))]
+pub use fallback::*;
+
+// input put back, e.g. read while waiting for replies to terminal queries
+static AHEAD: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// Terminal input, starting with anything put back with [`unread`]
+struct Stdin;
+
+pub fn stdin() -> impl Read {
+    Stdin
+}
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut ahead = AHEAD.lock().unwrap();
+        if !ahead.is_empty() {
+            let n = buf.len().min(ahead.len());
+            buf[..n].copy_from_slice(&ahead[..n]);
+            ahead.drain(..n);
+            return Ok(n);
+        }
+        drop(ahead);
+
+        read_stdin(buf)
+    }
+}
+
+/// Puts back input so that it's read again by [`stdin`]
+pub fn unread(bytes: &[u8]) {
+    AHEAD.lock().unwrap().extend_from_slice(bytes);
+}
+
+/// Takes out everything put back with [`unread`]
+pub fn take_unread() -> Vec<u8> {
+    std::mem::take(&mut AHEAD.lock().unwrap())
+}
+
+/// Reason [`wait`] returned
+#[cfg_attr(not(unix), allow(dead_code))]
+pub enum Wake {
+    /// Input is available (or the input was closed)
+    Input,
+    /// A signal was received, see [`take_resize`], [`take_continue`] and [`take_interrupt`]
+    Signal,
+    /// The timeout elapsed first
+    Timeout,
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    use super::{Wake, AHEAD};
+
+    static PIPE: OnceLock<Option<[libc::c_int; 2]>> = OnceLock::new();
+    // the signal handler can't go through `PIPE`, so the write end is stored separately
+    static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+    static RESIZED: AtomicBool = AtomicBool::new(false);
+    static CONTINUED: AtomicBool = AtomicBool::new(false);
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    const HANDLED: &[libc::c_int] = &[libc::SIGWINCH, libc::SIGCONT];
+    const TERMINATING: &[libc::c_int] = &[libc::SIGHUP, libc::SIGINT, libc::SIGQUIT, libc::SIGTERM];
+
+    /// Unbuffered terminal input
+    ///
+    /// [`io::Stdin`] has its own buffer, which hides pending input from `poll`.
+    pub(super) fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    /// Signal handlers installed for the duration of a read
+    pub struct Signals {
+        old: Vec<(libc::c_int, libc::sigaction)>,
+    }
+
+    impl Signals {
+        pub fn install() -> Self {
+            if pipe().is_none() {
+                return Self { old: Vec::new() };
+            }
+
+            let handler = on_signal as *const () as libc::sighandler_t;
+            Self::handle(HANDLED, handler, libc::SA_RESTART)
+        }
+
+        /// Handler for SIGINT, for reading lines outside raw mode, where Ctrl-C isn't read as input
+        pub fn interrupt() -> Self {
+            if pipe().is_none() {
+                return Self { old: Vec::new() };
+            }
+
+            INTERRUPTED.store(false, Ordering::Relaxed);
+            let handler = on_signal as *const () as libc::sighandler_t;
+            Self::handle(&[libc::SIGINT], handler, libc::SA_RESTART)
+        }
+
+        /// Handlers restoring the terminal before the process is killed, installed while in raw
+        /// mode. Signals the program handles itself are left alone
+        pub fn terminate() -> Self {
+            let handler = on_terminate as *const () as libc::sighandler_t;
+            let signals = TERMINATING.iter().copied().filter(|&sig| unsafe {
+                let mut prev = std::mem::zeroed::<libc::sigaction>();
+                libc::sigaction(sig, std::ptr::null(), &mut prev) == 0
+                    && prev.sa_sigaction == libc::SIG_DFL
+            });
+            Self::handle(&signals.collect::<Vec<_>>(), handler, libc::SA_RESETHAND)
+        }
+
+        fn handle(
+            signals: &[libc::c_int],
+            handler: libc::sighandler_t,
+            flags: libc::c_int,
+        ) -> Self {
+            let mut old = Vec::new();
+            unsafe {
+                let mut action = std::mem::zeroed::<libc::sigaction>();
+                action.sa_sigaction = handler;
+                action.sa_flags = flags;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                for &sig in signals {
+                    let mut prev = std::mem::zeroed();
+                    if libc::sigaction(sig, &action, &mut prev) == 0 {
+                        old.push((sig, prev));
+                    }
+                }
+            }
+
+            Self { old }
+        }
+    }
+
+    impl Drop for Signals {
+        fn drop(&mut self) {
+            for (sig, prev) in &self.old {
+                unsafe { libc::sigaction(*sig, prev, std::ptr::null_mut()) };
+            }
+        }
+    }
+
+    extern "C" fn on_signal(sig: libc::c_int) {
+        match sig {
+            libc::SIGWINCH => RESIZED.store(true, Ordering::Relaxed),
+            libc::SIGCONT => CONTINUED.store(true, Ordering::Relaxed),
+            libc::SIGINT => INTERRUPTED.store(true, Ordering::Relaxed),
+            _ => {}
+        }
+
+        wake();
+    }
+
+    extern "C" fn on_terminate(sig: libc::c_int) {
+        restore();
+        // the default action was put back with `SA_RESETHAND`, and runs once this returns
+        unsafe { libc::raise(sig) };
+    }
+
+    /// Takes the terminal out of raw mode and resets any modes left enabled. Only does what's
+    /// safe to do in a signal handler
+    pub fn restore() {
+        rawrrr::disable_raw();
+
+        let reset = b"\x1b[?2026l";
+        let fd = match unsafe { libc::isatty(libc::STDOUT_FILENO) } {
+            1 => libc::STDOUT_FILENO,
+            _ => libc::STDERR_FILENO,
+        };
+        unsafe { libc::write(fd, reset.as_ptr().cast(), reset.len()) };
+    }
+
+    /// Interrupts [`wait`], as if a signal was received
+    pub fn wake() {
+        let fd = WAKE_FD.load(Ordering::Relaxed);
+        if fd >= 0 {
+            // if the pipe is full, there's already a wakeup pending anyway
+            unsafe { libc::write(fd, [0_u8].as_ptr().cast(), 1) };
+        }
+    }
+
+    fn pipe() -> Option<[libc::c_int; 2]> {
+        *PIPE.get_or_init(|| unsafe {
+            let mut fds = [0; 2];
+            if libc::pipe(fds.as_mut_ptr()) == -1 {
+                return None;
+            }
+            for fd in fds {
+                libc::fcntl(
+                    fd,
+                    libc::F_SETFL,
+                    libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK,
+                );
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+            WAKE_FD.store(fds[1], Ordering::Relaxed);
+            Some(fds)
+        })
+    }
+
+    /// Returns `true` if [`wait`] can actually wait for input, and times out
+    pub fn can_wait() -> bool {
+        pipe().is_some()
+    }
+
+    /// Blocks until stdin is readable, a signal arrives, or the timeout elapses
+    pub fn wait(timeout: Option<Duration>) -> io::Result<Wake> {
+        let Some([wake, _]) = pipe() else {
+            return Ok(Wake::Input);
+        };
+        if !AHEAD.lock().unwrap().is_empty() {
+            return Ok(Wake::Input);
+        }
+        let timeout = timeout.map_or(-1, |t| t.as_millis().try_into().unwrap_or(libc::c_int::MAX));
+
+        let mut fds = [
+            libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: wake,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+
+        loop {
+            match unsafe { libc::poll(fds.as_mut_ptr(), 2, timeout) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(err);
+                }
+                0 => return Ok(Wake::Timeout),
+                _ => {}
+            }
+
+            if fds[1].revents != 0 {
+                let mut buf = [0_u8; 64];
+                while unsafe { libc::read(wake, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+                return Ok(Wake::Signal);
+            }
+
+            return Ok(Wake::Input);
+        }
+    }
+
+    /// Stops the process, as Ctrl-Z does outside raw mode, and puts the terminal back in raw mode
+    /// once it's continued
+    #[cfg(feature = "suspend")]
+    pub fn suspend() {
+        // SIGTSTP is what usually happens -- the process gets put in the background
+        unsafe { libc::kill(std::process::id() as i32, libc::SIGTSTP) };
+        rawrrr::enable_raw();
+        // ...which was done already
+        CONTINUED.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the terminal was resized since the last call
+    pub fn take_resize() -> bool {
+        RESIZED.swap(false, Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the process was stopped and continued since the last call
+    pub fn take_continue() -> bool {
+        CONTINUED.swap(false, Ordering::Relaxed)
+    }
+
+    /// Returns `true` if SIGINT was received since the last call, see [`Signals::interrupt`]
+    pub fn take_interrupt() -> bool {
+        INTERRUPTED.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    use std::io::{self, Read};
+    use std::time::Duration;
+
+    use super::Wake;
+
+    pub(super) fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+        io::stdin().lock().read(buf)
+    }
+
+    pub struct Signals;
+
+    impl Signals {
+        pub fn install() -> Self {
+            Self
+        }
+
+        pub fn interrupt() -> Self {
+            Self
+        }
+
+        pub fn terminate() -> Self {
+            Self
+        }
+    }
+
+    pub fn restore() {
+        rawrrr::disable_raw();
+    }
+
+    pub fn wake() {}
+
+    pub fn can_wait() -> bool {
+        false
+    }
+
+    /// Input can't be waited on here, so reading is left to block and `timeout` is ignored
+    pub fn wait(timeout: Option<Duration>) -> io::Result<Wake> {
+        let _ = timeout;
+        Ok(Wake::Input)
+    }
+
+    pub fn take_resize() -> bool {
+        false
+    }
+
+    pub fn take_continue() -> bool {
+        false
+    }
+
+    pub fn take_interrupt() -> bool {
+        false
+    }
+}
@@ -0,0 +1,171 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Terminal feature detection
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::ansi::{Reader, Reply};
+use crate::sys;
+
+/// How long to wait for the terminal to reply to queries
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+static SYNC: OnceLock<bool> = OnceLock::new();
+/// Set once the terminal fails to answer a query, so the next ones don't wait in vain
+static SILENT: AtomicBool = AtomicBool::new(false);
+
+/// Returns `true` if the terminal supports [synchronized output], asking it on first use.
+///
+/// [synchronized output]: https://gist.github.com/christianparpart/d8a62cc1ab659194337d73e399004036
+pub fn sync_output(w: &mut impl Write) -> io::Result<bool> {
+    if let Some(&sync) = SYNC.get() {
+        return Ok(sync);
+    }
+    if silent() {
+        return Ok(*SYNC.get_or_init(|| false));
+    }
+
+    // DECRQM for mode 2026, followed by DA1, which pretty much every terminal answers
+    write!(w, "\x1b[?2026$p\x1b[c")?;
+    w.flush()?;
+
+    let mut sync = false;
+    read_replies(|reply| match reply {
+        Reply::Mode { mode: 2026, state } => {
+            sync = matches!(state, 1 | 2);
+            true
+        }
+        _ => false,
+    })?;
+
+    Ok(*SYNC.get_or_init(|| sync))
+}
+
+/// Moves to a new line if the cursor isn't at the start of one, marking the end of any output
+/// left there with a `%` like zsh does.
+pub fn line_start(w: &mut impl Write) -> io::Result<()> {
+    if silent() {
+        return Ok(());
+    }
+
+    // DSR for the cursor position, followed by DA1 in case it isn't answered
+    write!(w, "\x1b[6n\x1b[c")?;
+    w.flush()?;
+
+    let mut col = None;
+    read_replies(|reply| match reply {
+        Reply::Position { col: c, .. } => {
+            col = Some(c);
+            true
+        }
+        _ => false,
+    })?;
+
+    if col.is_some_and(|col| col > 1) {
+        write!(w, "\x1b[7m%\x1b[m\r\n")?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if the terminal doesn't understand escape sequences, as with `TERM=dumb`
+pub fn is_dumb() -> bool {
+    static DUMB: OnceLock<bool> = OnceLock::new();
+    *DUMB.get_or_init(|| std::env::var_os("TERM").is_some_and(|term| term == "dumb"))
+}
+
+/// Returns `true` if colors are disabled with [`NO_COLOR`](https://no-color.org)
+pub fn no_color() -> bool {
+    static NO_COLOR: OnceLock<bool> = OnceLock::new();
+    *NO_COLOR.get_or_init(|| std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()))
+}
+
+/// Removes SGR sequences from `s`
+pub fn strip_sgr(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("\x1b[") {
+        out.push_str(&rest[..start]);
+        let params = &rest[start + 2..];
+        match params.find(|c| !matches!(c, '0'..='9' | ';' | ':')) {
+            Some(end) if params[end..].starts_with('m') => rest = &params[end + 1..],
+            _ => {
+                out.push_str(&rest[start..start + 2]);
+                rest = params;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Returns `true` if queries shouldn't be sent, since replies can't be waited for
+fn silent() -> bool {
+    !sys::can_wait() || is_dumb() || SILENT.load(Ordering::Relaxed)
+}
+
+/// Reads replies to queries from stdin until the terminal answers DA1, which must be the last
+/// query sent, or it takes too long to answer, in which case it's assumed not to answer any.
+///
+/// Replies are passed to `f`, which returns `false` for unexpected ones. Those, and anything else
+/// read, are considered user input, and will be read again afterwards. Since position reports
+/// can't be told apart from some keys, only the last one before DA1's reply is passed to `f`.
+fn read_replies(mut f: impl FnMut(Reply) -> bool) -> io::Result<()> {
+    // input put back earlier came before the replies, and may end with an incomplete sequence
+    let mut ahead = sys::take_unread();
+    let mut r = Reader::new(sys::stdin());
+    // the latest position report, and where its bytes are in `ahead`
+    let mut position = None;
+    let deadline = Instant::now() + TIMEOUT;
+
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match sys::wait(Some(timeout))? {
+            sys::Wake::Input => {}
+            sys::Wake::Signal => continue,
+            sys::Wake::Timeout => {
+                SILENT.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        match Reply::parse(r.read_sequence()?) {
+            Some(Reply::Attributes) => {
+                if let Some((reply, range)) = position {
+                    if f(reply) {
+                        ahead.drain(range);
+                    }
+                }
+                break;
+            }
+            Some(reply @ Reply::Position { .. }) => {
+                position = Some((reply, ahead.len()..ahead.len() + r.raw().len()));
+                ahead.extend_from_slice(r.raw());
+            }
+            Some(reply) if f(reply) => {}
+            _ => ahead.extend_from_slice(r.raw()),
+        }
+    }
+
+    sys::unread(&ahead);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip() {
+        assert_eq!(strip_sgr("plain"), "plain");
+        assert_eq!(strip_sgr("\x1b[1;32m>>\x1b[m "), ">> ");
+        assert_eq!(strip_sgr("\x1b[38:5:208mé\x1b[0m"), "é");
+        // anything else is left alone
+        assert_eq!(strip_sgr("\x1b[2Ka\x1b["), "\x1b[2Ka\x1b[");
+    }
+}
@@ -0,0 +1,449 @@
+// pomprt, a line editor prompt library
+// Copyright (c) 2023 rini
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Choice widgets, drawn in place of a prompt's input
+
+use std::io::{self, IsTerminal, Write};
+
+use crate::ansi::Reader;
+use crate::prompt::RawMode;
+use crate::render::{self, Frame, Renderer};
+use crate::state::term_size;
+use crate::{sys, term, Editor, Error, Event, Prompt};
+
+/// What a widget wants after handling an event
+enum Step<T> {
+    Pending,
+    /// The event was rejected
+    Bell,
+    /// The widget is done, with its output and how to show it
+    Done(T, String),
+}
+
+trait Widget {
+    type Output;
+
+    /// Lays out everything after the prompt, returning where the cursor goes. At most `rows` rows
+    /// should be added below the prompt
+    fn draw(&mut self, editor: &impl Editor, frame: &mut Frame, rows: usize) -> (usize, usize);
+
+    fn event(&mut self, event: Event) -> Result<Step<Self::Output>, Error>;
+
+    /// Reads an answer from a line of input, when stdin is not a terminal
+    fn parse(&self, line: &str) -> Option<Self::Output>;
+
+    /// Plain text to show before and after the prompt on dumb terminals, for typing in a line
+    fn describe(&self) -> (String, &str);
+}
+
+impl<E: Editor> Prompt<'_, E> {
+    /// Ask a yes or no question, using the prompt as the question
+    ///
+    /// `y` and `n` answer right away, and Enter picks `default`. If stdin is not a terminal, a
+    /// line is read instead, like [`read`](Prompt::read).
+    ///
+    /// ```no_run
+    /// if pomprt::new("Overwrite file? ").confirm(false)? {
+    ///     // ...
+    /// }
+    /// # Ok::<(), pomprt::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`read`](Prompt::read)
+    pub fn confirm(&mut self, default: bool) -> Result<bool, Error> {
+        self.run(Confirm { default })
+    }
+
+    /// Ask to pick one of `items`, returning its index
+    ///
+    /// Choices are navigated with the arrow keys, and typing filters them. If stdin is not a
+    /// terminal, a line with either the choice or its number (starting from 1) is read instead.
+    ///
+    /// ```no_run
+    /// let shells = ["bash", "fish", "zsh"];
+    /// let picked = pomprt::new("Shell: ").select(&shells)?;
+    /// println!("{}", shells[picked]);
+    /// # Ok::<(), pomprt::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`io::ErrorKind::InvalidInput`] if `items` is empty. Otherwise, see
+    /// [`read`](Prompt::read)
+    pub fn select(&mut self, items: &[impl AsRef<str>]) -> Result<usize, Error> {
+        self.run(Choices::new(items, false)?)
+            .map(|picked| picked[0])
+    }
+
+    /// Ask to pick any number of `items`, returning their indices in order
+    ///
+    /// Works like [`select`](Prompt::select), with Space or Tab toggling the current choice. If
+    /// stdin is not a terminal, choices are separated by commas.
+    ///
+    /// # Errors
+    ///
+    /// See [`select`](Prompt::select)
+    pub fn multi_select(&mut self, items: &[impl AsRef<str>]) -> Result<Vec<usize>, Error> {
+        self.run(Choices::new(items, true)?)
+    }
+
+    fn run<W: Widget>(&mut self, widget: W) -> Result<W::Output, Error> {
+        if !io::stdin().is_terminal() {
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Err(Error::Eof);
+            }
+            return widget.parse(line.trim()).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "invalid choice").into()
+            });
+        }
+
+        match (io::stdout().is_terminal(), term::is_dumb()) {
+            (true, false) => self.run_on(widget, io::stdout().lock()),
+            (false, false) => self.run_on(widget, io::stderr().lock()),
+            (true, true) => self.run_dumb(widget, io::stdout().lock()),
+            (false, true) => self.run_dumb(widget, io::stderr().lock()),
+        }
+    }
+
+    /// Line-oriented fallback for terminals that don't understand escape sequences
+    fn run_dumb<W: Widget>(&mut self, widget: W, mut w: impl Write) -> Result<W::Output, Error> {
+        let (before, after) = widget.describe();
+        let prompt = term::strip_sgr(&self.editor.highlight_prompt(&self.prompt, false));
+        loop {
+            write!(w, "{before}{prompt}{after}")?;
+            w.flush()?;
+
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Err(Error::Eof);
+            }
+            if let Some(output) = widget.parse(line.trim()) {
+                return Ok(output);
+            }
+        }
+    }
+
+    fn run_on<W: Widget>(&mut self, mut widget: W, output: impl Write) -> Result<W::Output, Error> {
+        let _raw = RawMode::acquire();
+        let _signals = sys::Signals::install();
+        let mut r = Reader::new(sys::stdin());
+        let mut w = io::BufWriter::new(output);
+        let mut screen = Renderer::new(term::sync_output(&mut w)?);
+        term::line_start(&mut w)?;
+
+        loop {
+            let (width, height) = term_size();
+            screen.resize(width);
+            let mut frame = self.frame(width, height);
+            let cursor = widget.draw(&self.editor, &mut frame, height.saturating_sub(1).max(1));
+            screen.render(&mut w, frame, cursor)?;
+            w.flush()?;
+
+            // redraw on signals, picking up resizes
+            if !matches!(sys::wait(None)?, sys::Wake::Input) {
+                if sys::take_continue() {
+                    rawrrr::enable_raw();
+                    screen.reset();
+                }
+                continue;
+            }
+
+            let event = self.editor.next_event(&mut r)?;
+            match event {
+                #[cfg(all(unix, feature = "suspend"))]
+                Event::Suspend => {
+                    sys::suspend();
+                    screen.reset();
+                    continue;
+                }
+                #[cfg(feature = "abort")]
+                Event::Abort => {
+                    rawrrr::disable_raw();
+                    std::process::abort()
+                }
+                _ => {}
+            }
+
+            let (result, answer) = match widget.event(event) {
+                Ok(Step::Pending) => continue,
+                Ok(Step::Bell) => {
+                    write!(w, "\x07")?;
+                    continue;
+                }
+                Ok(Step::Done(output, answer)) => (Ok(output), answer),
+                Err(e) => (Err(e), String::new()),
+            };
+
+            let mut frame = self.frame(width, height);
+            frame.print(&answer);
+            screen.finish(&mut w, frame)?;
+            w.flush()?;
+            return result;
+        }
+    }
+
+    fn frame(&self, width: usize, height: usize) -> Frame {
+        let mut frame = Frame::new(width, height, false);
+        frame.print(&self.editor.highlight_prompt(&self.prompt, false));
+        frame.print("\x1b[m");
+        frame.anchor();
+        frame
+    }
+}
+
+struct Confirm {
+    default: bool,
+}
+
+impl Widget for Confirm {
+    type Output = bool;
+
+    fn draw(&mut self, editor: &impl Editor, frame: &mut Frame, _rows: usize) -> (usize, usize) {
+        let choices = if self.default { "[Y/n] " } else { "[y/N] " };
+        frame.print(&editor.highlight_hint(choices));
+        frame.print("\x1b[m");
+        frame.end()
+    }
+
+    fn event(&mut self, event: Event) -> Result<Step<bool>, Error> {
+        let answer = match event {
+            Event::Insert('y' | 'Y') => true,
+            Event::Insert('n' | 'N') => false,
+            Event::Enter => self.default,
+            Event::Insert(_) => return Ok(Step::Bell),
+            Event::Interrupt => return Err(Error::Interrupt),
+            Event::Eof => return Err(Error::Eof),
+            _ => return Ok(Step::Pending),
+        };
+
+        let shown = if answer { "yes" } else { "no" };
+        Ok(Step::Done(answer, shown.to_owned()))
+    }
+
+    fn describe(&self) -> (String, &str) {
+        let choices = if self.default { "[Y/n] " } else { "[y/N] " };
+        (String::new(), choices)
+    }
+
+    fn parse(&self, line: &str) -> Option<bool> {
+        match line.to_ascii_lowercase().as_str() {
+            "" => Some(self.default),
+            "y" | "yes" => Some(true),
+            "n" | "no" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+struct Choices<'i, S> {
+    items: &'i [S],
+    multi: bool,
+    filter: String,
+    /// Indices of items matching the filter
+    matches: Vec<usize>,
+    /// Position in `matches` under the cursor
+    current: usize,
+    /// First match shown when they don't all fit on screen
+    top: usize,
+    picked: Vec<bool>,
+}
+
+impl<'i, S: AsRef<str>> Choices<'i, S> {
+    fn new(items: &'i [S], multi: bool) -> io::Result<Self> {
+        if items.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no choices"));
+        }
+
+        Ok(Self {
+            items,
+            multi,
+            filter: String::new(),
+            matches: (0..items.len()).collect(),
+            current: 0,
+            top: 0,
+            picked: vec![false; items.len()],
+        })
+    }
+
+    fn refilter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.matches = (0..self.items.len())
+            .filter(|&i| self.items[i].as_ref().to_lowercase().contains(&filter))
+            .collect();
+        self.current = 0;
+    }
+
+    fn find(&self, choice: &str) -> Option<usize> {
+        let choice = choice.trim();
+        match choice.parse::<usize>() {
+            Ok(n) => (1..=self.items.len()).contains(&n).then(|| n - 1),
+            Err(_) => self.items.iter().position(|item| item.as_ref() == choice),
+        }
+    }
+}
+
+impl<S: AsRef<str>> Widget for Choices<'_, S> {
+    type Output = Vec<usize>;
+
+    fn draw(&mut self, editor: &impl Editor, frame: &mut Frame, rows: usize) -> (usize, usize) {
+        frame.print(&self.filter);
+        let cursor = frame.end();
+
+        if self.matches.is_empty() {
+            frame.print("\n");
+            frame.print(&editor.highlight_hint("no matches"));
+            return cursor;
+        }
+
+        let len = self.matches.len();
+        let size = rows.min(len);
+        self.top = render::window(self.current, len, size, self.top);
+        for pos in self.top..self.top + size {
+            frame.print("\n\x1b[m");
+            if size >= 3 && pos == self.top && pos > 0 {
+                frame.print(&format!("\x1b[2m↑ {} more", pos + 1));
+                continue;
+            }
+            if size >= 3 && pos == self.top + size - 1 && pos < len - 1 {
+                frame.print(&format!("\x1b[2m↓ {} more", len - pos));
+                continue;
+            }
+
+            let i = self.matches[pos];
+            frame.print(if pos == self.current { "> " } else { "  " });
+            if self.multi {
+                frame.print(if self.picked[i] { "[x] " } else { "[ ] " });
+            }
+            frame.print(&editor.highlight_choice(self.items[i].as_ref(), pos == self.current));
+        }
+
+        cursor
+    }
+
+    fn event(&mut self, event: Event) -> Result<Step<Vec<usize>>, Error> {
+        let len = self.matches.len();
+        match event {
+            Event::Up if len > 0 => self.current = (self.current + len - 1) % len,
+            Event::Down if len > 0 => self.current = (self.current + 1) % len,
+            Event::Insert(' ') | Event::Tab if self.multi && len > 0 => {
+                let i = self.matches[self.current];
+                self.picked[i] = !self.picked[i];
+            }
+            Event::Insert(c) => {
+                self.filter.push(c);
+                self.refilter();
+            }
+            Event::Backspace => {
+                if self.filter.pop().is_none() {
+                    return Ok(Step::Bell);
+                }
+                self.refilter();
+            }
+            Event::Enter if self.multi => {
+                let picked: Vec<_> = (0..self.items.len()).filter(|&i| self.picked[i]).collect();
+                let answer = picked.iter().map(|&i| self.items[i].as_ref());
+                let answer = answer.collect::<Vec<_>>().join(", ");
+                return Ok(Step::Done(picked, answer));
+            }
+            Event::Enter if len > 0 => {
+                let i = self.matches[self.current];
+                return Ok(Step::Done(vec![i], self.items[i].as_ref().to_owned()));
+            }
+            Event::Enter => return Ok(Step::Bell),
+            Event::Interrupt if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.refilter();
+            }
+            Event::Interrupt => return Err(Error::Interrupt),
+            Event::Eof => return Err(Error::Eof),
+            _ => {}
+        }
+
+        Ok(Step::Pending)
+    }
+
+    fn describe(&self) -> (String, &str) {
+        let mut list = String::new();
+        for (i, item) in self.items.iter().enumerate() {
+            list.push_str(&format!("{}) {}\n", i + 1, item.as_ref()));
+        }
+        (list, if self.multi { "(comma-separated) " } else { "" })
+    }
+
+    fn parse(&self, line: &str) -> Option<Vec<usize>> {
+        if !self.multi {
+            return self.find(line).map(|i| vec![i]);
+        }
+        if line.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let mut picked = line
+            .split(',')
+            .map(|c| self.find(c))
+            .collect::<Option<Vec<_>>>()?;
+        picked.sort_unstable();
+        picked.dedup();
+        Some(picked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHELLS: [&str; 3] = ["bash", "fish", "zsh"];
+
+    #[test]
+    fn parse_confirm() {
+        let confirm = Confirm { default: true };
+        assert_eq!(confirm.parse(""), Some(true));
+        assert_eq!(confirm.parse("N"), Some(false));
+        assert_eq!(confirm.parse("yes"), Some(true));
+        assert_eq!(confirm.parse("maybe"), None);
+    }
+
+    #[test]
+    fn parse_select() {
+        let select = Choices::new(&SHELLS, false).unwrap();
+        assert_eq!(select.parse("fish"), Some(vec![1]));
+        assert_eq!(select.parse("3"), Some(vec![2]));
+        assert_eq!(select.parse("0"), None);
+        assert_eq!(select.parse("4"), None);
+        assert_eq!(select.parse("csh"), None);
+        assert_eq!(select.parse(""), None);
+    }
+
+    #[test]
+    fn parse_multi_select() {
+        let multi = Choices::new(&SHELLS, true).unwrap();
+        assert_eq!(multi.parse(""), Some(vec![]));
+        assert_eq!(multi.parse("zsh, 1"), Some(vec![0, 2]));
+        assert_eq!(multi.parse("2,fish"), Some(vec![1]));
+        assert_eq!(multi.parse("bash,csh"), None);
+    }
+
+    #[test]
+    fn filtering() {
+        let mut select = Choices::new(&SHELLS, false).unwrap();
+        assert!(matches!(select.event(Event::Up), Ok(Step::Pending)));
+        assert_eq!(select.current, 2);
+
+        select.event(Event::Insert('S')).ok();
+        assert_eq!(select.matches, [0, 1, 2]);
+        assert_eq!(select.current, 0);
+        select.event(Event::Insert('h')).ok();
+        select.event(Event::Down).ok();
+        assert!(matches!(select.event(Event::Enter), Ok(Step::Done(picked, _)) if picked == [1]));
+    }
+
+    #[test]
+    fn no_choices() {
+        assert!(Choices::new(&[] as &[&str], false).is_err());
+    }
+}